@@ -1,70 +1,775 @@
+use std::future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-use bytes::Bytes;
-use mini_redis::{Connection, Frame};
-use mini_redis_rs::shard_db::ShardDb;
-use tokio::net::{TcpListener, TcpStream};
+use mini_redis_rs::cmd::{Parse, ParseError};
+use mini_redis_rs::tls::{self, TlsAcceptor};
+use mini_redis_rs::{
+    join, Command, Connection, Db, Frame, Protocol, ProtocolError, Shutdown, Timeouts,
+};
+use socket2::{SockRef, TcpKeepalive};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{broadcast, mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio::time;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Result<T> = std::result::Result<T, Error>;
 
-type Db = Arc<ShardDb<String, Bytes>>;
+/// How long a TLS client over the `maxclients` limit has to complete the
+/// handshake, so that it can be told why it is turned away.
+const REJECT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// State shared by every connection.
+struct Shared {
+    db: Db,
+    config: Config,
+    stats: Stats,
+
+    /// One permit per client that may be connected, as set by `maxclients`.
+    clients: Arc<Semaphore>,
+
+    /// Asks `main` to shut the server down, in response to `SHUTDOWN`.
+    shutdown: mpsc::Sender<SaveMode>,
+}
+
+/// State of a single connection, besides the `Connection` itself.
+struct Handler {
+    shared: Arc<Shared>,
+
+    /// Notified when the server shuts down.
+    shutdown: Shutdown,
+
+    /// Counts the client towards `maxclients` until the connection ends,
+    /// whichever way it does.
+    _permit: OwnedSemaphorePermit,
+
+    /// Not used directly. `main` knows every connection is done once all the
+    /// handlers, and so all the clones of this sender, have been dropped.
+    _shutdown_complete: mpsc::Sender<()>,
+}
+
+/// Whether the data is saved to `dbfilename` when shutting down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SaveMode {
+    /// Save if a `dbfilename` is configured.
+    Default,
+    Save,
+    NoSave,
+}
+
+/// Counters reported by `INFO`.
+#[derive(Debug, Default)]
+struct Stats {
+    /// Number of connections accepted.
+    total_connections_received: AtomicU64,
+
+    /// Number of connections closed for exceeding one of the timeouts.
+    timedout_connections: AtomicU64,
+
+    /// Number of connections rejected because of `maxclients`.
+    rejected_connections: AtomicU64,
+}
+
+/// Server options, given on the command line the way they are written in
+/// `redis.conf`, e.g. `server --port 0 --unixsocket /tmp/redis.sock`.
+#[derive(Debug)]
+struct Config {
+    /// TCP port to listen on, or `0` not to listen on TCP.
+    port: u16,
+
+    /// Path of the Unix domain socket to listen on, if any.
+    unixsocket: Option<PathBuf>,
+
+    /// Permissions given to the Unix domain socket, in octal.
+    unixsocketperm: Option<u32>,
+
+    /// TCP port to accept TLS connections on, or `0` not to accept any.
+    tls_port: u16,
+
+    /// PEM file holding the server's certificate chain.
+    tls_cert_file: Option<PathBuf>,
+
+    /// PEM file holding the server's private key.
+    tls_key_file: Option<PathBuf>,
+
+    /// PEM file holding the certificate authorities client certificates are
+    /// checked against. When set, TLS clients must present a certificate.
+    tls_ca_cert_file: Option<PathBuf>,
+
+    /// Seconds a client may stay idle before being disconnected, or `0` to
+    /// never disconnect idle clients.
+    timeout: u64,
+
+    /// Seconds a client may take to send a frame once it started sending it,
+    /// or `0` for no limit.
+    read_timeout: u64,
+
+    /// Seconds writing a reply may take, or `0` for no limit.
+    write_timeout: u64,
+
+    /// Seconds between TCP keepalive probes on idle client sockets, or `0`
+    /// not to send any.
+    tcp_keepalive: u64,
+
+    /// Maximum number of clients connected at the same time. Clients over
+    /// the limit are disconnected right away.
+    maxclients: usize,
+
+    /// Seconds connections are given to finish their current command when
+    /// shutting down, before being dropped.
+    shutdown_timeout: u64,
+
+    /// File the data is loaded from at startup and saved to on shutdown, if
+    /// any.
+    dbfilename: Option<PathBuf>,
+}
+
+impl Config {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Config> {
+        let mut config = Config {
+            port: mini_redis_rs::DEFAULT_PORT.parse()?,
+            unixsocket: None,
+            unixsocketperm: None,
+            tls_port: 0,
+            tls_cert_file: None,
+            tls_key_file: None,
+            tls_ca_cert_file: None,
+            timeout: 0,
+            read_timeout: 0,
+            write_timeout: 0,
+            tcp_keepalive: 300,
+            maxclients: 10000,
+            shutdown_timeout: 10,
+            dbfilename: None,
+        };
+
+        while let Some(name) = args.next() {
+            let option = name.trim_start_matches("--");
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for '{}'", name))?;
+
+            match option {
+                "port" => config.port = value.parse()?,
+                "unixsocket" => config.unixsocket = Some(value.into()),
+                "unixsocketperm" => config.unixsocketperm = Some(u32::from_str_radix(&value, 8)?),
+                "tls-port" => config.tls_port = value.parse()?,
+                "tls-cert-file" => config.tls_cert_file = Some(value.into()),
+                "tls-key-file" => config.tls_key_file = Some(value.into()),
+                "tls-ca-cert-file" => config.tls_ca_cert_file = Some(value.into()),
+                "timeout" => config.timeout = value.parse()?,
+                "read-timeout" => config.read_timeout = value.parse()?,
+                "write-timeout" => config.write_timeout = value.parse()?,
+                "tcp-keepalive" => config.tcp_keepalive = value.parse()?,
+                "maxclients" => config.maxclients = value.parse()?,
+                "shutdown-timeout" => config.shutdown_timeout = value.parse()?,
+                "dbfilename" => config.dbfilename = Some(value.into()),
+                _ => return Err(format!("unknown option '{}'", name).into()),
+            }
+        }
+
+        if config.port == 0 && config.tls_port == 0 && config.unixsocket.is_none() {
+            return Err("no port nor unix socket to listen on".into());
+        }
+
+        if config.maxclients == 0 {
+            return Err("maxclients must be at least 1".into());
+        }
+
+        if config.tls_port != 0 && (config.tls_cert_file.is_none() || config.tls_key_file.is_none())
+        {
+            return Err("tls-port requires tls-cert-file and tls-key-file".into());
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the timeouts applied to client connections.
+    fn timeouts(&self) -> Timeouts {
+        let seconds = |secs| Some(Duration::from_secs(secs)).filter(|_| secs != 0);
+
+        Timeouts {
+            idle: seconds(self.timeout),
+            read: seconds(self.read_timeout),
+            write: seconds(self.write_timeout),
+        }
+    }
+
+    /// Returns the interval between TCP keepalive probes.
+    fn keepalive(&self) -> Option<Duration> {
+        Some(Duration::from_secs(self.tcp_keepalive)).filter(|_| self.tcp_keepalive != 0)
+    }
+}
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
-    let addr = format!("127.0.0.1:{}", mini_redis_rs::DEFAULT_PORT);
+    let config = Config::from_args(std::env::args().skip(1))?;
+
+    // Bind the listeners. Like Redis, port `0` disables TCP.
+    let tcp = if config.port != 0 {
+        let addr = format!("127.0.0.1:{}", config.port);
+        let listener = TcpListener::bind(&addr).await?;
+
+        println!("Listening on {}", addr);
+        Some(listener)
+    } else {
+        None
+    };
+
+    let tls = if config.tls_port != 0 {
+        let tls_config = tls::server_config(
+            config.tls_cert_file.as_deref().unwrap(),
+            config.tls_key_file.as_deref().unwrap(),
+            config.tls_ca_cert_file.as_deref(),
+        )?;
+
+        let addr = format!("127.0.0.1:{}", config.tls_port);
+        let listener = TcpListener::bind(&addr).await?;
+
+        println!("Listening on {} (TLS)", addr);
+        Some((listener, TlsAcceptor::from(Arc::new(tls_config))))
+    } else {
+        None
+    };
+
+    let unix = match &config.unixsocket {
+        Some(path) => Some(bind_unix(path, config.unixsocketperm)?),
+        None => None,
+    };
+
+    let keepalive = config.keepalive();
+    let shutdown_timeout = Duration::from_secs(config.shutdown_timeout);
+
+    let db = Db::new();
+    if let Some(path) = &config.dbfilename {
+        load(&db, path).await?;
+    }
+
+    // `SHUTDOWN` asks for the server to stop through this channel. Only the
+    // first request matters, so there is no point in queueing more.
+    let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
 
-    // Bind the listener to the address
-    let listener = TcpListener::bind(&addr).await?;
+    let shared = Arc::new(Shared {
+        db,
+        clients: Arc::new(Semaphore::new(config.maxclients)),
+        config,
+        stats: Stats::default(),
+        shutdown: shutdown_tx,
+    });
 
-    println!("Listening on {}", addr);
+    // Every connection is notified of the shutdown through `notify_shutdown`,
+    // and holds a clone of `shutdown_complete_tx` until it is done.
+    let (notify_shutdown, _) = broadcast::channel(1);
+    let (shutdown_complete_tx, mut shutdown_complete_rx) = mpsc::channel::<()>(1);
 
-    let shard_db = Arc::new(ShardDb::new(8));
+    // A client takes one of the `maxclients` permits as soon as it is
+    // accepted, so that clients still doing the TLS handshake count too.
+    // There is no handler for clients over the limit.
+    let handler = || match shared.clients.clone().try_acquire_owned() {
+        Ok(permit) => Some(Handler {
+            shared: shared.clone(),
+            shutdown: Shutdown::new(notify_shutdown.subscribe()),
+            _permit: permit,
+            _shutdown_complete: shutdown_complete_tx.clone(),
+        }),
+        Err(_) => {
+            shared
+                .stats
+                .rejected_connections
+                .fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    };
 
+    let mut sigterm = signal(SignalKind::terminate())?;
+
+    // Accept clients until the server is asked to stop.
+    let save = tokio::select! {
+        () = run(&tcp, &tls, &unix, keepalive, handler) => unreachable!(),
+        _ = tokio::signal::ctrl_c() => {
+            println!("Received SIGINT, shutting down");
+            SaveMode::Default
+        }
+        _ = sigterm.recv() => {
+            println!("Received SIGTERM, shutting down");
+            SaveMode::Default
+        }
+        Some(save) = shutdown_rx.recv() => {
+            println!("SHUTDOWN requested, shutting down");
+            save
+        }
+    };
+
+    // Stop accepting clients, then tell the connected ones to stop. Dropping
+    // our own `shutdown_complete_tx` leaves only the connections' clones, so
+    // `recv` returns `None` once they have all finished.
+    drop((tcp, tls, unix));
+    drop(notify_shutdown);
+    drop(shutdown_complete_tx);
+
+    if time::timeout(shutdown_timeout, shutdown_complete_rx.recv())
+        .await
+        .is_err()
+    {
+        eprintln!("some connections did not finish in time, dropping them");
+    }
+
+    let dbfilename = shared.config.dbfilename.as_deref();
+    match (save, dbfilename) {
+        (SaveMode::NoSave, _) | (_, None) => {}
+        (_, Some(path)) => {
+            save_db(&shared.db, path).await?;
+            println!("DB saved to {}", path.display());
+        }
+    }
+
+    if let Some(path) = &shared.config.unixsocket {
+        let _ = std::fs::remove_file(path);
+    }
+
+    Ok(())
+}
+
+/// Accepts clients on every listener, spawning a task to serve each of them.
+///
+/// Never returns: failing to accept a client, e.g. because the server ran
+/// out of file descriptors, does not stop it from accepting the next ones.
+async fn run(
+    tcp: &Option<TcpListener>,
+    tls: &Option<(TcpListener, TlsAcceptor)>,
+    unix: &Option<UnixListener>,
+    keepalive: Option<Duration>,
+    handler: impl Fn() -> Option<Handler>,
+) {
     loop {
-        let (socket, _) = listener.accept().await?;
+        // All kinds of clients go through the same `process` handler.
+        tokio::select! {
+            res = accept_tcp(tcp) => {
+                let socket = match res {
+                    Ok(socket) => socket,
+                    Err(err) => {
+                        accept_failed(err).await;
+                        continue;
+                    }
+                };
+                set_keepalive(&socket, keepalive);
+
+                let handler = match handler() {
+                    Some(handler) => handler,
+                    None => {
+                        tokio::spawn(reject(socket));
+                        continue;
+                    }
+                };
+
+                tokio::spawn(async move {
+                    process(socket, handler).await;
+                });
+            }
+            res = accept_tls(tls) => {
+                let (socket, acceptor) = match res {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        accept_failed(err).await;
+                        continue;
+                    }
+                };
+                set_keepalive(&socket, keepalive);
 
-        let shard_db = shard_db.clone();
+                // The error can only be sent once the handshake is done, which
+                // a client over the limit is not given long for.
+                let mut handler = match handler() {
+                    Some(handler) => handler,
+                    None => {
+                        tokio::spawn(async move {
+                            let handshake = acceptor.accept(socket);
+
+                            if let Ok(Ok(stream)) = time::timeout(REJECT_HANDSHAKE_TIMEOUT, handshake).await {
+                                reject(stream).await;
+                            }
+                        });
+                        continue;
+                    }
+                };
+
+                // The handshake is done by the connection's task so that a
+                // slow client does not hold up accepting the others, nor the
+                // shutdown.
+                tokio::spawn(async move {
+                    let res = tokio::select! {
+                        res = acceptor.accept(socket) => res,
+                        _ = handler.shutdown.recv() => return,
+                    };
+
+                    match res {
+                        Ok(stream) => process(stream, handler).await,
+                        Err(err) => eprintln!("TLS handshake failed: {}", err),
+                    }
+                });
+            }
+            res = accept_unix(unix) => {
+                let socket = match res {
+                    Ok(socket) => socket,
+                    Err(err) => {
+                        accept_failed(err).await;
+                        continue;
+                    }
+                };
 
-        tokio::spawn(async move {
-            process(socket, shard_db).await;
-        });
+                let handler = match handler() {
+                    Some(handler) => handler,
+                    None => {
+                        tokio::spawn(reject(socket));
+                        continue;
+                    }
+                };
+
+                tokio::spawn(async move {
+                    process(socket, handler).await;
+                });
+            }
+        }
+    }
+}
+
+/// Logs an error accepting a client, then waits a little before accepting
+/// the next one.
+///
+/// Errors such as `EMFILE` persist until some connections are closed, so
+/// retrying right away would only spin.
+async fn accept_failed(err: Error) {
+    eprintln!("failed to accept: {}", err);
+    time::sleep(Duration::from_millis(100)).await;
+}
+
+/// Turns away a client over the `maxclients` limit. Like Redis, the client
+/// is told why.
+async fn reject<S>(socket: S)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut connection = Connection::new(socket);
+
+    let err = Frame::Error("ERR max number of clients reached".to_string());
+    if connection.write_frame(&err).await.is_ok() {
+        // Over TLS, this lets the client know nothing was cut off.
+        let _ = connection.get_mut().shutdown().await;
     }
 }
 
-async fn process(socket: TcpStream, db: Db) {
-    use mini_redis::Command::{self, Get, Set};
+/// Loads the data saved by `save_db` at `path`, if the file exists.
+///
+/// The commands it holds are replayed, with their replies discarded.
+async fn load(db: &Db, path: &Path) -> Result<()> {
+    let file = match File::open(path).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut connection = Connection::new(file);
+    let mut replies = Connection::new(join(tokio::io::empty(), tokio::io::sink()));
+    let mut commands = 0;
+
+    // The file only holds commands that return right away, nothing waits for
+    // this to be notified.
+    let (_notify, shutdown) = broadcast::channel(1);
+    let mut shutdown = Shutdown::new(shutdown);
+
+    while let Some(frame) = connection.read_frame().await? {
+        Command::from_frame(frame)?
+            .apply(db, &mut replies, &mut shutdown)
+            .await?;
+        commands += 1;
+    }
+
+    println!("DB loaded from {}: {} commands", path.display(), commands);
+    Ok(())
+}
+
+/// Saves every key to `path`, as the `SET` commands that recreate them, the
+/// way an append only file would.
+///
+/// The data is written to a temporary file first, then renamed, so that a
+/// crash while saving does not lose the previous save.
+async fn save_db(db: &Db, path: &Path) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut connection = Connection::new(File::create(&tmp).await?);
+    connection.set_protocol(Protocol::Resp2);
+
+    for commands in db.dump() {
+        connection.write_frames(&commands).await?;
+    }
+
+    connection.get_mut().sync_all().await?;
+    tokio::fs::rename(&tmp, path).await?;
+
+    Ok(())
+}
+
+/// Enables TCP keepalive on `socket`, so that the connection to a client that
+/// went away without closing it is eventually reset, even when the client
+/// has no idle timeout.
+///
+/// Failing to do so is no reason to turn the client away, nor to stop
+/// accepting the others: the error is logged and the client is served
+/// without keepalive.
+fn set_keepalive(socket: &TcpStream, keepalive: Option<Duration>) {
+    if let Some(keepalive) = keepalive {
+        // Like Redis, probes are sent every third of the keepalive time once
+        // the connection has been idle for that long.
+        let params = TcpKeepalive::new()
+            .with_time(keepalive)
+            .with_interval(keepalive / 3);
+
+        if let Err(err) = SockRef::from(socket).set_tcp_keepalive(&params) {
+            eprintln!("failed to enable TCP keepalive: {}", err);
+        }
+    }
+}
+
+/// Binds a Unix domain socket at `path`, and sets its permissions to `perm`.
+///
+/// A socket file left behind by a previous run is removed first.
+fn bind_unix(path: &Path, perm: Option<u32>) -> Result<UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    let listener = UnixListener::bind(path)?;
+
+    if let Some(perm) = perm {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(perm))?;
+    }
+
+    println!("Listening on {}", path.display());
+    Ok(listener)
+}
+
+/// Accepts the next TCP client, or waits forever without a TCP listener.
+async fn accept_tcp(listener: &Option<TcpListener>) -> Result<TcpStream> {
+    match listener {
+        Some(listener) => Ok(listener.accept().await?.0),
+        None => future::pending().await,
+    }
+}
+
+/// Accepts the next TLS client, returning it with the acceptor to do the TLS
+/// handshake with. Waits forever without a TLS listener.
+async fn accept_tls(tls: &Option<(TcpListener, TlsAcceptor)>) -> Result<(TcpStream, TlsAcceptor)> {
+    match tls {
+        Some((listener, acceptor)) => Ok((listener.accept().await?.0, acceptor.clone())),
+        None => future::pending().await,
+    }
+}
+
+/// Accepts the next Unix socket client, or waits forever without a Unix
+/// socket listener.
+async fn accept_unix(listener: &Option<UnixListener>) -> Result<UnixStream> {
+    match listener {
+        Some(listener) => Ok(listener.accept().await?.0),
+        None => future::pending().await,
+    }
+}
+
+/// Serves the commands of a single client until it disconnects. The client
+/// may be connected over any transport.
+async fn process<S>(socket: S, handler: Handler)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let Handler {
+        shared,
+        mut shutdown,
+        _permit,
+        _shutdown_complete,
+    } = handler;
+    let db = &shared.db;
+    let stats = &shared.stats;
 
     // The `Connection` lets us read/write redis **frames** instead of byte
-    // streams. The `Connection` type is defined by min-redis.
+    // streams. The `Connection` type is defined in this crate so that it can
+    // speak both RESP2 and RESP3.
     let mut connection = Connection::new(socket);
+    connection.set_timeouts(shared.config.timeouts());
+
+    stats
+        .total_connections_received
+        .fetch_add(1, Ordering::Relaxed);
 
     // Use `read_frame()` to receive a command from the connection.
-    while let Some(frame) = connection.read_frame().await.unwrap() {
-        let response = match Command::from_frame(frame).unwrap() {
-            Set(cmd) => {
-                let mut shard = db.get(cmd.key().to_string()).lock().unwrap();
+    loop {
+        // While waiting for the next command, also listen for the shutdown
+        // signal. A command that was read is always carried out, so only
+        // idle clients are interrupted.
+        let res = tokio::select! {
+            res = connection.read_frame() => res,
+            _ = shutdown.recv() => {
+                // Let the client know why the connection is closed. Replies
+                // still queued are sent first.
+                let notice = Frame::Error("ERR Server is shutting down".to_string());
+                let _ = connection.write_frame(&notice).await;
+                return;
+            }
+        };
+
+        let frame = match res {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return,
+            Err(err) => return close(&mut connection, err, stats).await,
+        };
 
-                shard.insert(cmd.key().to_string(), cmd.value().clone());
-                Frame::Simple("OK".to_string())
+        // An empty inline command, i.e. a blank line typed into telnet, is
+        // ignored like Redis does.
+        if matches!(&frame, Frame::Array(parts) if parts.is_empty()) {
+            continue;
+        }
+
+        // `INFO` and `SHUTDOWN` are about the server rather than the data:
+        // they need its configuration, counters and listeners, which the
+        // library's `Command` has no access to, so they are handled here.
+        // Their arguments are parsed with the same `Parse`, so that errors
+        // are replied to like for any other command.
+        //
+        // Replies are queued rather than written. Replies to pipelined
+        // commands are sent together once every command that was received
+        // has been handled.
+        let res: Result<()> = if is_command(&frame, "info") {
+            connection
+                .queue_frame(&info(&shared))
+                .await
+                .map_err(Into::into)
+        } else if is_command(&frame, "shutdown") {
+            match shutdown_command(frame, &shared) {
+                // There is no reply: the client is notified when the server
+                // actually shuts down.
+                Ok(()) => continue,
+                Err(err) => connection
+                    .queue_frame(&err.to_frame())
+                    .await
+                    .map_err(Into::into),
             }
-            Get(cmd) => {
-                let shard = db.get(cmd.key().to_string()).lock().unwrap();
-
-                if let Some(value) = shard.get(cmd.key()) {
-                    // `Frame::Bulk` expects data to be of type `Bytes`.
-                    Frame::Bulk(value.clone().into())
-                } else {
-                    Frame::Null
-                }
+        } else {
+            match Command::from_frame(frame) {
+                Ok(cmd) => cmd.apply(db, &mut connection, &mut shutdown).await,
+                // A malformed command gets an error reply, and the client can
+                // go on with the next one.
+                Err(err) => connection
+                    .queue_frame(&err.to_frame())
+                    .await
+                    .map_err(Into::into),
             }
-            cmd => panic!("unimplemented command: {:?}", cmd),
         };
 
-        // Write the response to the client.
-        connection
-            .write_frame(&response)
-            .await
-            .expect("Failed to write frame to connection");
+        // Commands may read from the connection too, e.g. in subscriber mode,
+        // so their errors are handled like read errors.
+        if let Err(err) = res {
+            return close(&mut connection, err, stats).await;
+        }
+    }
+}
+
+/// Ends a connection that failed with `err`.
+async fn close<S>(connection: &mut Connection<S>, err: Error, stats: &Stats)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // The client sent a frame that cannot be decoded, for example one
+    // exceeding the protocol limits. The rest of the stream cannot be
+    // trusted, so like Redis, reply with the reason and close the connection.
+    if let Some(err) = err.downcast_ref::<ProtocolError>() {
+        eprintln!("closing connection: {}", err);
+
+        let _ = connection.write_frame(&err.to_frame()).await;
+    }
+
+    // A client that exceeded one of the timeouts is dropped without a reply.
+    if is_timeout(&*err) {
+        stats.timedout_connections.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns `true` if `err` is an I/O error caused by one of the connection's
+/// timeouts.
+fn is_timeout(err: &(dyn std::error::Error + 'static)) -> bool {
+    matches!(
+        err.downcast_ref::<std::io::Error>(),
+        Some(err) if err.kind() == std::io::ErrorKind::TimedOut
+    )
+}
+
+/// Handles `INFO`, replying with the server's clients and statistics.
+fn info(shared: &Shared) -> Frame {
+    let stats = &shared.stats;
+    let maxclients = shared.config.maxclients;
+
+    let info = format!(
+        "# Clients\r\n\
+         connected_clients:{}\r\n\
+         maxclients:{}\r\n\
+         \r\n\
+         # Stats\r\n\
+         total_connections_received:{}\r\n\
+         rejected_connections:{}\r\n\
+         timedout_connections:{}\r\n",
+        maxclients - shared.clients.available_permits(),
+        maxclients,
+        stats.total_connections_received.load(Ordering::Relaxed),
+        stats.rejected_connections.load(Ordering::Relaxed),
+        stats.timedout_connections.load(Ordering::Relaxed),
+    );
+
+    Frame::Bulk(info.into())
+}
+
+/// Handles `SHUTDOWN [NOSAVE|SAVE]`, asking `main` to shut the server down.
+///
+/// Returns the error to reply with if the shutdown cannot be done.
+fn shutdown_command(frame: Frame, shared: &Shared) -> std::result::Result<(), ParseError> {
+    let mut parse = Parse::new(frame)?;
+
+    let save = match parse.remaining() {
+        0 => SaveMode::Default,
+        _ => match parse.next_string()?.to_lowercase().as_str() {
+            "nosave" => SaveMode::NoSave,
+            "save" => SaveMode::Save,
+            _ => return Err(ParseError::Syntax),
+        },
+    };
+
+    // Like Redis, extra arguments are a syntax error rather than a wrong
+    // number of arguments.
+    if parse.remaining() > 0 {
+        return Err(ParseError::Syntax);
+    }
+
+    if save == SaveMode::Save && shared.config.dbfilename.is_none() {
+        return Err(ParseError::Other(
+            "no dbfilename configured to SAVE to".to_string(),
+        ));
+    }
+
+    // The channel being full means a shutdown is already underway.
+    let _ = shared.shutdown.try_send(save);
+    Ok(())
+}
+
+/// Returns `true` if `frame` is a command array whose name is `name`.
+fn is_command(frame: &Frame, name: &str) -> bool {
+    match frame {
+        Frame::Array(parts) => match parts.first() {
+            Some(Frame::Bulk(cmd)) => cmd.eq_ignore_ascii_case(name.as_bytes()),
+            _ => false,
+        },
+        _ => false,
     }
 }
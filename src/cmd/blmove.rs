@@ -0,0 +1,115 @@
+use crate::cmd::bpop::{wait, Wait};
+use crate::cmd::lmove::parse_end;
+use crate::cmd::{parse_timeout, Parse, ParseError};
+use crate::db::{BlockingPop, End};
+use crate::{Connection, Db, Frame, Shutdown};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Blocking variant of `LMOVE`: if the source list is empty, block until a
+/// value is pushed to it, then move that value. The value is popped and
+/// pushed at once, so other clients never find it missing from both lists.
+///
+/// The reply is the value moved, or nil if the timeout expires first.
+#[derive(Debug)]
+pub struct BLMove {
+    /// Name of the list the value is popped from
+    source: String,
+
+    /// Name of the list the value is pushed to
+    destination: String,
+
+    /// End of the source list the value is popped from
+    from: End,
+
+    /// End of the destination list the value is pushed to
+    to: End,
+
+    /// How long to block for at most, `None` to block forever
+    timeout: Option<Duration>,
+}
+
+impl BLMove {
+    /// Create a new `BLMove` command which moves a value from the `from` end
+    /// of `source` to the `to` end of `destination`, blocking for at most
+    /// `timeout`.
+    pub fn new(
+        source: impl ToString,
+        destination: impl ToString,
+        from: End,
+        to: End,
+        timeout: Option<Duration>,
+    ) -> BLMove {
+        BLMove {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            from,
+            to,
+            timeout,
+        }
+    }
+
+    /// Parse a `BLMove` instance from a received frame.
+    ///
+    /// The `BLMOVE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// BLMOVE source destination LEFT|RIGHT LEFT|RIGHT timeout
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<BLMove, ParseError> {
+        let source = parse.next_string()?;
+        let destination = parse.next_string()?;
+        let from = parse_end(parse)?;
+        let to = parse_end(parse)?;
+        let timeout = parse_timeout(parse)?;
+
+        Ok(BLMove {
+            source,
+            destination,
+            from,
+            to,
+            timeout,
+        })
+    }
+
+    /// Apply the `BLMove` command to the specified `Db` instance.
+    ///
+    /// If the client disconnects or the server shuts down while the command
+    /// is blocked, there is no reply.
+    pub(crate) async fn apply<S>(
+        self,
+        db: &Db,
+        dst: &mut Connection<S>,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        // When the source has a value, this is a plain `LMOVE`.
+        let moved = match db.lmove_or_block(&self.source, &self.destination, self.from, self.to) {
+            Ok(BlockingPop::Popped(_, value)) => Ok(value),
+            Ok(BlockingPop::Blocked(waiter)) => {
+                match wait(waiter, self.timeout, dst, shutdown).await? {
+                    Wait::Served(moved) => moved.map(|(_, value)| value),
+                    // Like every blocking list command timing out in Redis.
+                    Wait::TimedOut => {
+                        dst.queue_frame(&Frame::NullArray).await?;
+                        return Ok(());
+                    }
+                    Wait::Gone => return Ok(()),
+                }
+            }
+            Err(err) => Err(err),
+        };
+
+        let response = match moved {
+            Ok(value) => Frame::Bulk(value),
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
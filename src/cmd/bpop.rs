@@ -0,0 +1,163 @@
+use crate::cmd::{parse_timeout, Parse, ParseError};
+use crate::db::{BlockingPop, End, Waiter};
+use crate::value::WrongType;
+use crate::{Connection, Db, Frame, Shutdown};
+use bytes::Bytes;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::{select, time};
+
+/// Remove and return a value from the head of the first non-empty list among
+/// the given keys for `BLPOP`, from its tail for `BRPOP`, blocking until a
+/// value is pushed if they are all empty.
+///
+/// The reply is the key the value was popped from along with the value, or
+/// nil if the timeout expires first. Clients blocked on the same key are
+/// served in the order they blocked.
+#[derive(Debug)]
+pub struct BPop {
+    /// Names of the keys, in the order they are checked
+    keys: Vec<String>,
+
+    /// End of the lists the value is popped from
+    end: End,
+
+    /// How long to block for at most, `None` to block forever
+    timeout: Option<Duration>,
+}
+
+impl BPop {
+    /// Create a new `BPop` command which pops a value from the `end` of one of
+    /// the lists held by `keys`, blocking for at most `timeout`.
+    pub fn new(keys: &[String], end: End, timeout: Option<Duration>) -> BPop {
+        BPop {
+            keys: keys.to_vec(),
+            end,
+            timeout,
+        }
+    }
+
+    /// Parse a `BPop` instance from a received frame.
+    ///
+    /// The command name has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// BLPOP key [key ...] timeout
+    /// BRPOP key [key ...] timeout
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse, end: End) -> Result<BPop, ParseError> {
+        // At least one key is required, the last argument is the timeout.
+        let mut keys = vec![parse.next_string()?];
+
+        while parse.remaining() > 1 {
+            keys.push(parse.next_string()?);
+        }
+
+        let timeout = parse_timeout(parse)?;
+
+        Ok(BPop { keys, end, timeout })
+    }
+
+    /// Apply the `BPop` command to the specified `Db` instance.
+    ///
+    /// If the client disconnects or the server shuts down while the command
+    /// is blocked, there is no reply.
+    pub(crate) async fn apply<S>(
+        self,
+        db: &Db,
+        dst: &mut Connection<S>,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let popped = match db.pop_or_block(&self.keys, self.end) {
+            Ok(BlockingPop::Popped(key, value)) => Ok((key, value)),
+            Ok(BlockingPop::Blocked(waiter)) => {
+                match wait(waiter, self.timeout, dst, shutdown).await? {
+                    Wait::Served(popped) => popped,
+                    // Like every blocking list command timing out in Redis.
+                    Wait::TimedOut => {
+                        dst.queue_frame(&Frame::NullArray).await?;
+                        return Ok(());
+                    }
+                    Wait::Gone => return Ok(()),
+                }
+            }
+            Err(err) => Err(err),
+        };
+
+        let response = match popped {
+            Ok((key, value)) => {
+                Frame::Array(vec![Frame::Bulk(Bytes::from(key)), Frame::Bulk(value)])
+            }
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+
+    /// Returns the command name
+    pub fn get_name(&self) -> &str {
+        match self.end {
+            End::Left => "blpop",
+            End::Right => "brpop",
+        }
+    }
+}
+
+/// How waiting for a blocked client to be served ended.
+pub(crate) enum Wait {
+    /// The client was served the key a value was popped from along with the
+    /// value, or the error preventing a `BLMOVE` client from moving it.
+    Served(Result<(String, Bytes), WrongType>),
+
+    /// The timeout expired.
+    TimedOut,
+
+    /// The client disconnected or the server is shutting down, in which case
+    /// the client should not be replied to.
+    Gone,
+}
+
+/// Waits for `waiter` to be served for at most `timeout`. Either way,
+/// dropping `waiter` stops blocking.
+pub(crate) async fn wait<S>(
+    mut waiter: Waiter,
+    timeout: Option<Duration>,
+    dst: &mut Connection<S>,
+    shutdown: &mut Shutdown,
+) -> crate::Result<Wait>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let timeout = async {
+        match timeout {
+            Some(timeout) => time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    // Like `tx.closed()` in `select.rs`, `dst.closed()` completes when the
+    // client goes away, which stops waiting for it.
+    //
+    // A client served as its timeout expires or the server shuts down is
+    // replied to, as the value may already have been moved for `BLMOVE`.
+    // The `Waiter` is checked first, then cancelled, in case it is served in
+    // between.
+    let waited = select! {
+        biased;
+        served = waiter.recv() => return Ok(Wait::Served(served)),
+        _ = timeout => Wait::TimedOut,
+        res = dst.closed() => {
+            res?;
+            return Ok(Wait::Gone);
+        }
+        _ = shutdown.recv() => Wait::Gone,
+    };
+
+    Ok(waiter.cancel().map_or(waited, Wait::Served))
+}
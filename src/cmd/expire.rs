@@ -0,0 +1,97 @@
+use crate::cmd::{deadline, Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
+
+/// Set a timeout on key. After the timeout has expired, the key is deleted.
+///
+/// The same command implements `EXPIRE`, `PEXPIRE`, `EXPIREAT` and
+/// `PEXPIREAT`, which only differ in how the timeout is given. A timeout in
+/// the past deletes the key right away.
+#[derive(Debug)]
+pub struct Expire {
+    /// Name of the key to expire
+    key: String,
+
+    /// When the key expires
+    when: Instant,
+
+    /// Whether the timeout was given in milliseconds rather than seconds
+    millis: bool,
+
+    /// Whether the timeout was given as a Unix timestamp rather than relative
+    /// to now
+    unix: bool,
+}
+
+impl Expire {
+    /// Create a new `Expire` command which makes `key` expire at `when`.
+    pub fn new(key: impl ToString, when: Instant) -> Expire {
+        Expire {
+            key: key.to_string(),
+            when,
+            millis: true,
+            unix: false,
+        }
+    }
+
+    /// Parse an `Expire` instance from a received frame.
+    ///
+    /// The command name has already been consumed. `millis` and `unix` tell
+    /// how it gives the timeout.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// EXPIRE key seconds
+    /// PEXPIRE key milliseconds
+    /// EXPIREAT key unix-time-seconds
+    /// PEXPIREAT key unix-time-milliseconds
+    /// ```
+    pub(crate) fn parse_frames(
+        parse: &mut Parse,
+        millis: bool,
+        unix: bool,
+    ) -> Result<Expire, ParseError> {
+        let key = parse.next_string()?;
+        let time = parse.next_int()?;
+
+        let when = if millis {
+            Some(time)
+        } else {
+            time.checked_mul(1000)
+        }
+        .and_then(|time| deadline(time, unix))
+        .ok_or_else(|| {
+            ParseError::Other(format!("invalid expire time in '{}' command", parse.name()))
+        })?;
+
+        Ok(Expire {
+            key,
+            when,
+            millis,
+            unix,
+        })
+    }
+
+    /// Returns the command name
+    pub fn get_name(&self) -> &str {
+        match (self.millis, self.unix) {
+            (false, false) => "expire",
+            (true, false) => "pexpire",
+            (false, true) => "expireat",
+            (true, true) => "pexpireat",
+        }
+    }
+
+    /// Apply the `Expire` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let set = db.expire(&self.key, self.when);
+
+        dst.queue_frame(&Frame::Integer(set.into())).await?;
+        Ok(())
+    }
+}
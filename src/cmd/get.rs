@@ -0,0 +1,58 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Get the value of key.
+///
+/// If the key does not exist the special value nil is returned.
+#[derive(Debug)]
+pub struct Get {
+    /// Name of the key to get
+    key: String,
+}
+
+impl Get {
+    /// Create a new `Get` command which fetches `key`.
+    pub fn new(key: impl ToString) -> Get {
+        Get {
+            key: key.to_string(),
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Parse a `Get` instance from a received frame.
+    ///
+    /// The `GET` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing two entries.
+    ///
+    /// ```text
+    /// GET key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Get, ParseError> {
+        let key = parse.next_string()?;
+
+        Ok(Get { key })
+    }
+
+    /// Apply the `Get` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.get(&self.key) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
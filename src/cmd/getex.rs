@@ -0,0 +1,74 @@
+use crate::cmd::{parse_expire_option, Parse, ParseError};
+use crate::db::Expiry;
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Get the value of key and optionally set its expiration.
+///
+/// If the key does not exist the special value nil is returned.
+#[derive(Debug)]
+pub struct GetEx {
+    /// Name of the key to get
+    key: String,
+
+    /// What happens to the key's expiration
+    expiry: Expiry,
+}
+
+impl GetEx {
+    /// Create a new `GetEx` command which fetches `key` and applies `expiry`
+    /// to it.
+    pub fn new(key: impl ToString, expiry: Expiry) -> GetEx {
+        GetEx {
+            key: key.to_string(),
+            expiry,
+        }
+    }
+
+    /// Parse a `GetEx` instance from a received frame.
+    ///
+    /// The `GETEX` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+    ///   PXAT unix-time-milliseconds | PERSIST]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<GetEx, ParseError> {
+        let key = parse.next_string()?;
+
+        let expiry = if parse.remaining() > 0 {
+            let option = parse.next_string()?.to_lowercase();
+
+            match option.as_str() {
+                "persist" => Expiry::Persist,
+                "ex" | "px" | "exat" | "pxat" => Expiry::At(parse_expire_option(parse, &option)?),
+                _ => return Err(ParseError::Syntax),
+            }
+        } else {
+            Expiry::Keep
+        };
+
+        if parse.remaining() > 0 {
+            return Err(ParseError::Syntax);
+        }
+
+        Ok(GetEx { key, expiry })
+    }
+
+    /// Apply the `GetEx` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.get_ex(&self.key, self.expiry) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Frame, Protocol};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Switch the connection to another protocol version.
+///
+/// Replies with a map describing the server, encoded with the new protocol.
+/// Without a version, the current protocol is kept.
+#[derive(Debug)]
+pub struct Hello {
+    /// The requested protocol version, if any.
+    protover: Option<i64>,
+}
+
+impl Hello {
+    /// Create a new `Hello` command, switching to `protover` if given.
+    pub fn new(protover: Option<i64>) -> Hello {
+        Hello { protover }
+    }
+
+    /// Parse a `Hello` instance from a received frame.
+    ///
+    /// The `HELLO` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// HELLO [protover]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Hello, ParseError> {
+        let protover = match parse.remaining() {
+            0 => None,
+            1 => Some(parse.next_int().map_err(|_| {
+                ParseError::Other("Protocol version is not an integer or out of range".to_string())
+            })?),
+            _ => {
+                return Err(ParseError::Other(
+                    "Syntax error in HELLO option".to_string(),
+                ))
+            }
+        };
+
+        Ok(Hello { protover })
+    }
+
+    /// Apply the `Hello` command to the connection.
+    pub(crate) async fn apply<S>(self, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let protocol = match self.protover {
+            None => dst.protocol(),
+            Some(version) => match Protocol::from_version(version) {
+                Some(protocol) => protocol,
+                None => {
+                    let err = Frame::Error("NOPROTO unsupported protocol version".to_string());
+                    dst.queue_frame(&err).await?;
+                    return Ok(());
+                }
+            },
+        };
+
+        dst.set_protocol(protocol);
+
+        let bulk = |s: &'static str| Frame::Bulk(Bytes::from_static(s.as_bytes()));
+
+        let response = Frame::Map(vec![
+            (bulk("server"), bulk("redis")),
+            (bulk("version"), bulk(env!("CARGO_PKG_VERSION"))),
+            (bulk("proto"), Frame::Integer(protocol.version())),
+            (bulk("mode"), bulk("standalone")),
+            (bulk("role"), bulk("master")),
+        ]);
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
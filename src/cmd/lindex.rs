@@ -0,0 +1,58 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Get the value at an index of a list.
+///
+/// Negative indexes count from the tail of the list: `-1` is the last value.
+/// Returns nil if the index is out of range or the key does not exist.
+#[derive(Debug)]
+pub struct LIndex {
+    /// Name of the key
+    key: String,
+
+    /// Index of the value
+    index: i64,
+}
+
+impl LIndex {
+    /// Create a new `LIndex` command which returns the value at `index` in the
+    /// list held by `key`.
+    pub fn new(key: impl ToString, index: i64) -> LIndex {
+        LIndex {
+            key: key.to_string(),
+            index,
+        }
+    }
+
+    /// Parse an `LIndex` instance from a received frame.
+    ///
+    /// The `LINDEX` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// LINDEX key index
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<LIndex, ParseError> {
+        let key = parse.next_string()?;
+        let index = parse.next_int()?;
+
+        Ok(LIndex { key, index })
+    }
+
+    /// Apply the `LIndex` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.lindex(&self.key, self.index) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Get the length of a list.
+///
+/// Returns `0` if the key does not exist.
+#[derive(Debug)]
+pub struct LLen {
+    /// Name of the key
+    key: String,
+}
+
+impl LLen {
+    /// Create a new `LLen` command which returns the length of the list held
+    /// by `key`.
+    pub fn new(key: impl ToString) -> LLen {
+        LLen {
+            key: key.to_string(),
+        }
+    }
+
+    /// Parse an `LLen` instance from a received frame.
+    ///
+    /// The `LLEN` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// LLEN key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<LLen, ParseError> {
+        let key = parse.next_string()?;
+
+        Ok(LLen { key })
+    }
+
+    /// Apply the `LLen` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.llen(&self.key) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
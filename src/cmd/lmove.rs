@@ -0,0 +1,85 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::End;
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Atomically pop a value from one end of a list and push it to an end of
+/// another list.
+///
+/// The destination list is created if it does not exist. The source and
+/// destination may be the same list, which rotates it. Returns the value
+/// moved, or nil if the source key does not exist.
+#[derive(Debug)]
+pub struct LMove {
+    /// Name of the list the value is popped from
+    source: String,
+
+    /// Name of the list the value is pushed to
+    destination: String,
+
+    /// End of the source list the value is popped from
+    from: End,
+
+    /// End of the destination list the value is pushed to
+    to: End,
+}
+
+impl LMove {
+    /// Create a new `LMove` command which moves a value from the `from` end of
+    /// `source` to the `to` end of `destination`.
+    pub fn new(source: impl ToString, destination: impl ToString, from: End, to: End) -> LMove {
+        LMove {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            from,
+            to,
+        }
+    }
+
+    /// Parse an `LMove` instance from a received frame.
+    ///
+    /// The `LMOVE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// LMOVE source destination LEFT|RIGHT LEFT|RIGHT
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<LMove, ParseError> {
+        let source = parse.next_string()?;
+        let destination = parse.next_string()?;
+        let from = parse_end(parse)?;
+        let to = parse_end(parse)?;
+
+        Ok(LMove {
+            source,
+            destination,
+            from,
+            to,
+        })
+    }
+
+    /// Apply the `LMove` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.lmove(&self.source, &self.destination, self.from, self.to) {
+            Ok(Some(value)) => Frame::Bulk(value),
+            Ok(None) => Frame::Null,
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
+
+/// Parses a `LEFT` or `RIGHT` argument.
+pub(crate) fn parse_end(parse: &mut Parse) -> Result<End, ParseError> {
+    match &parse.next_string()?.to_lowercase()[..] {
+        "left" => Ok(End::Left),
+        "right" => Ok(End::Right),
+        _ => Err(ParseError::Syntax),
+    }
+}
@@ -0,0 +1,63 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Get the values of a list from index `start` to `stop` included.
+///
+/// Indexes start at `0` for the head of the list, and negative indexes count
+/// from its tail: `-1` is the last value. Indexes out of range are not an
+/// error, they are clamped to the ends of the list.
+#[derive(Debug)]
+pub struct LRange {
+    /// Name of the key
+    key: String,
+
+    /// Index of the first value
+    start: i64,
+
+    /// Index of the last value
+    stop: i64,
+}
+
+impl LRange {
+    /// Create a new `LRange` command which returns the values of the list
+    /// held by `key` from `start` to `stop` included.
+    pub fn new(key: impl ToString, start: i64, stop: i64) -> LRange {
+        LRange {
+            key: key.to_string(),
+            start,
+            stop,
+        }
+    }
+
+    /// Parse an `LRange` instance from a received frame.
+    ///
+    /// The `LRANGE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// LRANGE key start stop
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<LRange, ParseError> {
+        let key = parse.next_string()?;
+        let start = parse.next_int()?;
+        let stop = parse.next_int()?;
+
+        Ok(LRange { key, start, stop })
+    }
+
+    /// Apply the `LRange` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.lrange(&self.key, self.start, self.stop) {
+            Ok(values) => Frame::Array(values.into_iter().map(Frame::Bulk).collect()),
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Remove the values equal to an element from a list.
+///
+/// With a positive count, at most that many values are removed, starting
+/// from the head of the list. With a negative count, starting from its tail.
+/// A count of `0` removes every matching value. Returns the number of values
+/// removed.
+#[derive(Debug)]
+pub struct LRem {
+    /// Name of the key
+    key: String,
+
+    /// How many values to remove, and from which end
+    count: i64,
+
+    /// Value to remove
+    value: Bytes,
+}
+
+impl LRem {
+    /// Create a new `LRem` command which removes `count` occurrences of
+    /// `value` from the list held by `key`.
+    pub fn new(key: impl ToString, count: i64, value: Bytes) -> LRem {
+        LRem {
+            key: key.to_string(),
+            count,
+            value,
+        }
+    }
+
+    /// Parse an `LRem` instance from a received frame.
+    ///
+    /// The `LREM` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// LREM key count element
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<LRem, ParseError> {
+        let key = parse.next_string()?;
+        let count = parse.next_int()?;
+        let value = parse.next_bytes()?;
+
+        Ok(LRem { key, count, value })
+    }
+
+    /// Apply the `LRem` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.lrem(&self.key, self.count, &self.value) {
+            Ok(removed) => Frame::Integer(removed as i64),
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
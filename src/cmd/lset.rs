@@ -0,0 +1,66 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Replace the value at an index of a list.
+///
+/// Negative indexes count from the tail of the list: `-1` is the last value.
+/// Unlike `LINDEX`, an index out of range is an error, as is a key that does
+/// not exist.
+#[derive(Debug)]
+pub struct LSet {
+    /// Name of the key
+    key: String,
+
+    /// Index of the value to replace
+    index: i64,
+
+    /// Value to store at `index`
+    value: Bytes,
+}
+
+impl LSet {
+    /// Create a new `LSet` command which sets the value at `index` in the list
+    /// held by `key` to `value`.
+    pub fn new(key: impl ToString, index: i64, value: Bytes) -> LSet {
+        LSet {
+            key: key.to_string(),
+            index,
+            value,
+        }
+    }
+
+    /// Parse an `LSet` instance from a received frame.
+    ///
+    /// The `LSET` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// LSET key index element
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<LSet, ParseError> {
+        let key = parse.next_string()?;
+        let index = parse.next_int()?;
+        let value = parse.next_bytes()?;
+
+        Ok(LSet { key, index, value })
+    }
+
+    /// Apply the `LSet` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.lset(&self.key, self.index, self.value) {
+            Ok(Some(true)) => Frame::Simple("OK".to_string()),
+            Ok(Some(false)) => Frame::Error("ERR index out of range".to_string()),
+            Ok(None) => Frame::Error("ERR no such key".to_string()),
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Trim a list so that it only keeps the values from index `start` to `stop`
+/// included.
+///
+/// Indexes are interpreted like `LRANGE` does. The key is deleted if no value
+/// is left.
+#[derive(Debug)]
+pub struct LTrim {
+    /// Name of the key
+    key: String,
+
+    /// Index of the first value to keep
+    start: i64,
+
+    /// Index of the last value to keep
+    stop: i64,
+}
+
+impl LTrim {
+    /// Create a new `LTrim` command which trims the list held by `key` to the
+    /// values from `start` to `stop` included.
+    pub fn new(key: impl ToString, start: i64, stop: i64) -> LTrim {
+        LTrim {
+            key: key.to_string(),
+            start,
+            stop,
+        }
+    }
+
+    /// Parse an `LTrim` instance from a received frame.
+    ///
+    /// The `LTRIM` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// LTRIM key start stop
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<LTrim, ParseError> {
+        let key = parse.next_string()?;
+        let start = parse.next_int()?;
+        let stop = parse.next_int()?;
+
+        Ok(LTrim { key, start, stop })
+    }
+
+    /// Apply the `LTrim` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.ltrim(&self.key, self.start, self.stop) {
+            Ok(()) => Frame::Simple("OK".to_string()),
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
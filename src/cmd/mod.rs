@@ -0,0 +1,343 @@
+//! Commands understood by the server.
+//!
+//! A command is parsed from the frame a client sent with
+//! `Command::from_frame`, then run against the `Db` with `Command::apply`,
+//! which replies on the client's `Connection`.
+
+mod parse;
+pub use parse::{Parse, ParseError};
+
+mod blmove;
+pub use blmove::BLMove;
+
+mod bpop;
+pub use bpop::BPop;
+
+mod expire;
+pub use expire::Expire;
+
+mod get;
+pub use get::Get;
+
+mod getex;
+pub use getex::GetEx;
+
+mod hello;
+pub use hello::Hello;
+
+mod lindex;
+pub use lindex::LIndex;
+
+mod llen;
+pub use llen::LLen;
+
+mod lmove;
+pub use lmove::LMove;
+
+mod lrange;
+pub use lrange::LRange;
+
+mod lrem;
+pub use lrem::LRem;
+
+mod lset;
+pub use lset::LSet;
+
+mod ltrim;
+pub use ltrim::LTrim;
+
+mod persist;
+pub use persist::Persist;
+
+mod ping;
+pub use ping::Ping;
+
+mod pop;
+pub use pop::Pop;
+
+mod publish;
+pub use publish::Publish;
+
+mod pubsub;
+pub use pubsub::PubSub;
+
+mod push;
+pub use push::Push;
+
+mod set;
+pub use set::Set;
+
+mod subscribe;
+pub use subscribe::{PSubscribe, PUnsubscribe, Subscribe, Unsubscribe};
+
+mod ttl;
+pub use ttl::Ttl;
+
+mod r#type;
+pub use r#type::Type;
+
+mod unknown;
+pub use unknown::Unknown;
+
+use crate::db::{unix_time, End};
+use crate::{Connection, Db, Frame, Shutdown};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
+
+/// Enumeration of supported Redis commands.
+///
+/// Methods called on `Command` are delegated to the command implementation.
+#[derive(Debug)]
+pub enum Command {
+    BLMove(BLMove),
+    BPop(BPop),
+    Expire(Expire),
+    Get(Get),
+    GetEx(GetEx),
+    Hello(Hello),
+    LIndex(LIndex),
+    LLen(LLen),
+    LMove(LMove),
+    LRange(LRange),
+    LRem(LRem),
+    LSet(LSet),
+    LTrim(LTrim),
+    Persist(Persist),
+    Ping(Ping),
+    Pop(Pop),
+    PSubscribe(PSubscribe),
+    Publish(Publish),
+    PubSub(PubSub),
+    PUnsubscribe(PUnsubscribe),
+    Push(Push),
+    Set(Set),
+    Subscribe(Subscribe),
+    Ttl(Ttl),
+    Type(Type),
+    Unsubscribe(Unsubscribe),
+    Unknown(Unknown),
+}
+
+impl Command {
+    /// Parse a command from a received frame.
+    ///
+    /// The `Frame` must be an array whose first entry is the command name.
+    /// Unknown commands are not an error: they parse to `Command::Unknown`,
+    /// which replies with an error when applied.
+    ///
+    /// # Returns
+    ///
+    /// On success, the command value is returned. If the arguments do not
+    /// match what the command expects, `Err` is returned, whose `to_frame`
+    /// is the reply to send to the client.
+    pub fn from_frame(frame: Frame) -> Result<Command, ParseError> {
+        // The frame value is decorated with `Parse`. `Parse` provides a
+        // "cursor" like API which makes parsing the command easier.
+        let mut parse = Parse::new(frame)?;
+
+        // Match the command name, delegating the rest of the parsing to the
+        // specific command.
+        let command = match parse.name() {
+            "blmove" => Command::BLMove(BLMove::parse_frames(&mut parse)?),
+            "blpop" => Command::BPop(BPop::parse_frames(&mut parse, End::Left)?),
+            "brpop" => Command::BPop(BPop::parse_frames(&mut parse, End::Right)?),
+            "expire" => Command::Expire(Expire::parse_frames(&mut parse, false, false)?),
+            "pexpire" => Command::Expire(Expire::parse_frames(&mut parse, true, false)?),
+            "expireat" => Command::Expire(Expire::parse_frames(&mut parse, false, true)?),
+            "pexpireat" => Command::Expire(Expire::parse_frames(&mut parse, true, true)?),
+            "get" => Command::Get(Get::parse_frames(&mut parse)?),
+            "getex" => Command::GetEx(GetEx::parse_frames(&mut parse)?),
+            "hello" => Command::Hello(Hello::parse_frames(&mut parse)?),
+            "lindex" => Command::LIndex(LIndex::parse_frames(&mut parse)?),
+            "llen" => Command::LLen(LLen::parse_frames(&mut parse)?),
+            "lmove" => Command::LMove(LMove::parse_frames(&mut parse)?),
+            "lpop" => Command::Pop(Pop::parse_frames(&mut parse, End::Left)?),
+            "lpush" => Command::Push(Push::parse_frames(&mut parse, End::Left)?),
+            "lrange" => Command::LRange(LRange::parse_frames(&mut parse)?),
+            "lrem" => Command::LRem(LRem::parse_frames(&mut parse)?),
+            "lset" => Command::LSet(LSet::parse_frames(&mut parse)?),
+            "ltrim" => Command::LTrim(LTrim::parse_frames(&mut parse)?),
+            "persist" => Command::Persist(Persist::parse_frames(&mut parse)?),
+            "ping" => Command::Ping(Ping::parse_frames(&mut parse)?),
+            "psubscribe" => Command::PSubscribe(PSubscribe::parse_frames(&mut parse)?),
+            "publish" => Command::Publish(Publish::parse_frames(&mut parse)?),
+            "pubsub" => Command::PubSub(PubSub::parse_frames(&mut parse)?),
+            "punsubscribe" => Command::PUnsubscribe(PUnsubscribe::parse_frames(&mut parse)?),
+            "rpop" => Command::Pop(Pop::parse_frames(&mut parse, End::Right)?),
+            "rpush" => Command::Push(Push::parse_frames(&mut parse, End::Right)?),
+            "set" => Command::Set(Set::parse_frames(&mut parse)?),
+            "subscribe" => Command::Subscribe(Subscribe::parse_frames(&mut parse)?),
+            "ttl" => Command::Ttl(Ttl::parse_frames(&mut parse, false)?),
+            "pttl" => Command::Ttl(Ttl::parse_frames(&mut parse, true)?),
+            "type" => Command::Type(Type::parse_frames(&mut parse)?),
+            "unsubscribe" => Command::Unsubscribe(Unsubscribe::parse_frames(&mut parse)?),
+            _ => {
+                // `return` is called here to skip the `finish()` call below.
+                // As the command is not recognized, its arguments are left
+                // unconsumed.
+                return Ok(Command::Unknown(Unknown::parse_frames(&mut parse)));
+            }
+        };
+
+        // Check if there is any remaining unconsumed argument. If there is,
+        // the command was given too many of them.
+        parse.finish()?;
+
+        Ok(command)
+    }
+
+    /// Apply the command to the specified `Db` instance.
+    ///
+    /// The response is queued on `dst`, to be sent along with the replies to
+    /// the other commands the client pipelined. An error is only returned if
+    /// the connection fails.
+    ///
+    /// `SUBSCRIBE` and `PSUBSCRIBE` keep the connection in subscriber mode
+    /// until the client unsubscribes from every channel and pattern, or
+    /// `shutdown` is notified. Blocking commands also return early when
+    /// `shutdown` is notified.
+    pub async fn apply<S>(
+        self,
+        db: &Db,
+        dst: &mut Connection<S>,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        use Command::*;
+
+        match self {
+            BLMove(cmd) => cmd.apply(db, dst, shutdown).await,
+            BPop(cmd) => cmd.apply(db, dst, shutdown).await,
+            Expire(cmd) => cmd.apply(db, dst).await,
+            Get(cmd) => cmd.apply(db, dst).await,
+            GetEx(cmd) => cmd.apply(db, dst).await,
+            Hello(cmd) => cmd.apply(dst).await,
+            LIndex(cmd) => cmd.apply(db, dst).await,
+            LLen(cmd) => cmd.apply(db, dst).await,
+            LMove(cmd) => cmd.apply(db, dst).await,
+            LRange(cmd) => cmd.apply(db, dst).await,
+            LRem(cmd) => cmd.apply(db, dst).await,
+            LSet(cmd) => cmd.apply(db, dst).await,
+            LTrim(cmd) => cmd.apply(db, dst).await,
+            Persist(cmd) => cmd.apply(db, dst).await,
+            Ping(cmd) => cmd.apply(dst).await,
+            Pop(cmd) => cmd.apply(db, dst).await,
+            PSubscribe(cmd) => cmd.apply(db, dst, shutdown).await,
+            Publish(cmd) => cmd.apply(db, dst).await,
+            PubSub(cmd) => cmd.apply(db, dst).await,
+            PUnsubscribe(cmd) => cmd.apply(dst).await,
+            Push(cmd) => cmd.apply(db, dst).await,
+            Set(cmd) => cmd.apply(db, dst).await,
+            Subscribe(cmd) => cmd.apply(db, dst, shutdown).await,
+            Ttl(cmd) => cmd.apply(db, dst).await,
+            Type(cmd) => cmd.apply(db, dst).await,
+            Unsubscribe(cmd) => cmd.apply(dst).await,
+            Unknown(cmd) => cmd.apply(dst).await,
+        }
+    }
+
+    /// Returns the command name
+    pub fn get_name(&self) -> &str {
+        match self {
+            Command::BLMove(_) => "blmove",
+            Command::BPop(cmd) => cmd.get_name(),
+            Command::Expire(cmd) => cmd.get_name(),
+            Command::Get(_) => "get",
+            Command::GetEx(_) => "getex",
+            Command::Hello(_) => "hello",
+            Command::LIndex(_) => "lindex",
+            Command::LLen(_) => "llen",
+            Command::LMove(_) => "lmove",
+            Command::LRange(_) => "lrange",
+            Command::LRem(_) => "lrem",
+            Command::LSet(_) => "lset",
+            Command::LTrim(_) => "ltrim",
+            Command::Persist(_) => "persist",
+            Command::Ping(_) => "ping",
+            Command::Pop(cmd) => cmd.get_name(),
+            Command::PSubscribe(_) => "psubscribe",
+            Command::Publish(_) => "publish",
+            Command::PubSub(cmd) => cmd.get_name(),
+            Command::PUnsubscribe(_) => "punsubscribe",
+            Command::Push(cmd) => cmd.get_name(),
+            Command::Set(_) => "set",
+            Command::Subscribe(_) => "subscribe",
+            Command::Ttl(cmd) => cmd.get_name(),
+            Command::Type(_) => "type",
+            Command::Unsubscribe(_) => "unsubscribe",
+            Command::Unknown(cmd) => cmd.get_name(),
+        }
+    }
+}
+
+/// Returns the instant at which a key given a timeout of `millis` expires.
+///
+/// With `unix`, the timeout is a Unix timestamp, otherwise it is relative to
+/// now. A timeout in the past gives an instant that has already passed. `None`
+/// is returned if the instant is too far away to be represented.
+pub(crate) fn deadline(millis: i64, unix: bool) -> Option<Instant> {
+    let millis = if unix {
+        i128::from(millis) - unix_time().as_millis() as i128
+    } else {
+        i128::from(millis)
+    };
+
+    let now = Instant::now();
+
+    match u64::try_from(millis) {
+        Ok(millis) => now.checked_add(Duration::from_millis(millis)),
+        Err(_) => Some(now),
+    }
+}
+
+/// Parses the argument of the `EX`, `PX`, `EXAT` or `PXAT` option, whose name
+/// is `option` in lower case, returning when the key expires.
+///
+/// Like Redis, times that are not positive are rejected.
+pub(crate) fn parse_expire_option(parse: &mut Parse, option: &str) -> Result<Instant, ParseError> {
+    if parse.remaining() == 0 {
+        return Err(ParseError::Syntax);
+    }
+
+    let time = parse.next_int()?;
+
+    let millis = match option {
+        "ex" | "exat" => time.checked_mul(1000),
+        _ => Some(time),
+    };
+
+    millis
+        .filter(|_| time > 0)
+        .and_then(|millis| deadline(millis, option.ends_with("at")))
+        .ok_or_else(|| {
+            ParseError::Other(format!("invalid expire time in '{}' command", parse.name()))
+        })
+}
+
+/// Parses the timeout of a blocking command, in seconds, which may have a
+/// fractional part.
+///
+/// Like Redis, a timeout of `0` blocks forever, which is returned as `None`.
+pub(crate) fn parse_timeout(parse: &mut Parse) -> Result<Option<Duration>, ParseError> {
+    let secs: f64 = parse
+        .next_string()?
+        .parse()
+        .ok()
+        .filter(|secs: &f64| secs.is_finite())
+        .ok_or_else(|| ParseError::Other("timeout is not a float or out of range".to_string()))?;
+
+    if secs < 0.0 {
+        return Err(ParseError::Other("timeout is negative".to_string()));
+    }
+
+    if secs == 0.0 {
+        return Ok(None);
+    }
+
+    Duration::try_from_secs_f64(secs)
+        .map(Some)
+        .map_err(|_| ParseError::Other("timeout is out of range".to_string()))
+}
@@ -0,0 +1,150 @@
+use crate::Frame;
+use bytes::Bytes;
+use std::{fmt, str, vec};
+
+/// Utility for parsing a command
+///
+/// Commands are represented as array frames. Each entry in the frame is a
+/// "token". A `Parse` is initialized with the array frame and provides a
+/// cursor-like API. Each command struct includes a `parse_frames` method that
+/// uses a `Parse` to extract its fields.
+#[derive(Debug)]
+pub struct Parse {
+    /// Name of the command, in lower case.
+    name: String,
+
+    /// Iterator over the arguments following the name.
+    parts: vec::IntoIter<Frame>,
+}
+
+/// Error encountered while parsing a command.
+///
+/// Parse errors do not end the connection: the client is sent the reply
+/// returned by `to_frame` and may go on with its next command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The frame is not an array of strings starting with a command name.
+    Protocol,
+
+    /// The command, whose name is given, has too few or too many arguments.
+    WrongArity(String),
+
+    /// An argument that must be an integer is not one, or is out of range.
+    NotInteger,
+
+    /// An argument is not valid for the command, e.g. an unknown option.
+    Syntax,
+
+    /// Any other error, described by the message.
+    Other(String),
+}
+
+impl Parse {
+    /// Create a new `Parse` to parse the contents of `frame`, reading the
+    /// command name.
+    ///
+    /// Returns `Err` if `frame` is not an array starting with a string.
+    pub fn new(frame: Frame) -> Result<Parse, ParseError> {
+        let mut parts = match frame {
+            Frame::Array(array) => array.into_iter(),
+            _ => return Err(ParseError::Protocol),
+        };
+
+        let name = match parts.next() {
+            Some(Frame::Bulk(name)) => String::from_utf8_lossy(&name).to_lowercase(),
+            Some(Frame::Simple(name)) => name.to_lowercase(),
+            _ => return Err(ParseError::Protocol),
+        };
+
+        Ok(Parse { name, parts })
+    }
+
+    /// Returns the command name, in lower case.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of arguments left.
+    pub fn remaining(&self) -> usize {
+        self.parts.len()
+    }
+
+    /// Return the next argument. Missing arguments are reported as a wrong
+    /// number of arguments.
+    fn next(&mut self) -> Result<Frame, ParseError> {
+        self.parts
+            .next()
+            .ok_or_else(|| ParseError::WrongArity(self.name.clone()))
+    }
+
+    /// Return the next argument as a string.
+    ///
+    /// If the argument is not valid UTF-8, an error is returned.
+    pub fn next_string(&mut self) -> Result<String, ParseError> {
+        let bytes = self.next_bytes()?;
+
+        str::from_utf8(&bytes)
+            .map(|s| s.to_string())
+            .map_err(|_| ParseError::Other("invalid UTF-8 string".to_string()))
+    }
+
+    /// Return the next argument as raw bytes.
+    pub fn next_bytes(&mut self) -> Result<Bytes, ParseError> {
+        match self.next()? {
+            Frame::Simple(s) => Ok(Bytes::from(s.into_bytes())),
+            Frame::Bulk(data) => Ok(data),
+            _ => Err(ParseError::Protocol),
+        }
+    }
+
+    /// Return the next argument as an integer.
+    ///
+    /// Like Redis, the whole argument must be a base 10 number fitting in an
+    /// `i64`.
+    pub fn next_int(&mut self) -> Result<i64, ParseError> {
+        let bytes = match self.next()? {
+            Frame::Integer(v) => return Ok(v),
+            Frame::Simple(s) => Bytes::from(s.into_bytes()),
+            Frame::Bulk(data) => data,
+            _ => return Err(ParseError::Protocol),
+        };
+
+        str::from_utf8(&bytes)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(ParseError::NotInteger)
+    }
+
+    /// Ensure there are no more arguments.
+    pub fn finish(&mut self) -> Result<(), ParseError> {
+        if self.parts.len() == 0 {
+            Ok(())
+        } else {
+            Err(ParseError::WrongArity(self.name.clone()))
+        }
+    }
+}
+
+impl ParseError {
+    /// Returns the error reply sent to the client, in the same format as
+    /// Redis.
+    pub fn to_frame(&self) -> Frame {
+        Frame::Error(format!("ERR {}", self))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Protocol => "Protocol error: expected an array of bulk strings".fmt(fmt),
+            ParseError::WrongArity(name) => {
+                write!(fmt, "wrong number of arguments for '{}' command", name)
+            }
+            ParseError::NotInteger => "value is not an integer or out of range".fmt(fmt),
+            ParseError::Syntax => "syntax error".fmt(fmt),
+            ParseError::Other(msg) => msg.fmt(fmt),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
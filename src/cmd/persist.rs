@@ -0,0 +1,48 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Remove the existing timeout on key.
+///
+/// Returns `1` if the timeout was removed, `0` if the key does not exist or
+/// has no timeout.
+#[derive(Debug)]
+pub struct Persist {
+    /// Name of the key
+    key: String,
+}
+
+impl Persist {
+    /// Create a new `Persist` command which removes the timeout of `key`.
+    pub fn new(key: impl ToString) -> Persist {
+        Persist {
+            key: key.to_string(),
+        }
+    }
+
+    /// Parse a `Persist` instance from a received frame.
+    ///
+    /// The `PERSIST` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// PERSIST key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Persist, ParseError> {
+        let key = parse.next_string()?;
+
+        Ok(Persist { key })
+    }
+
+    /// Apply the `Persist` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let removed = db.persist(&self.key);
+
+        dst.queue_frame(&Frame::Integer(removed.into())).await?;
+        Ok(())
+    }
+}
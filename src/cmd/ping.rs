@@ -0,0 +1,77 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Frame, Protocol};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Returns PONG if no argument is provided, otherwise returns a copy of the
+/// argument.
+///
+/// Unlike most commands, it is also allowed in subscriber mode, where clients
+/// use it to check the connection is alive.
+#[derive(Debug, Default)]
+pub struct Ping {
+    /// Optional message to be returned
+    msg: Option<Bytes>,
+}
+
+impl Ping {
+    /// Create a new `Ping` command with an optional `msg`.
+    pub fn new(msg: Option<Bytes>) -> Ping {
+        Ping { msg }
+    }
+
+    /// Parse a `Ping` instance from a received frame.
+    ///
+    /// The `PING` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// PING [message]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Ping, ParseError> {
+        let msg = match parse.remaining() {
+            0 => None,
+            _ => Some(parse.next_bytes()?),
+        };
+
+        Ok(Ping { msg })
+    }
+
+    /// Apply the `Ping` command, replying on `dst`.
+    pub(crate) async fn apply<S>(self, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match self.msg {
+            None => Frame::Simple("PONG".to_string()),
+            Some(msg) => Frame::Bulk(msg),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+
+    /// Apply the `Ping` command in subscriber mode.
+    ///
+    /// Like Redis, RESP2 clients are replied to with a `pong` push, which they
+    /// receive as an array like the messages, along with the message or an
+    /// empty string. RESP3 clients can tell replies and pushes apart, and get
+    /// the usual reply.
+    pub(crate) async fn apply_subscribed<S>(self, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        if dst.protocol() == Protocol::Resp3 {
+            return self.apply(dst).await;
+        }
+
+        let response = Frame::Push(vec![
+            Frame::Bulk(Bytes::from_static(b"pong")),
+            Frame::Bulk(self.msg.unwrap_or_default()),
+        ]);
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::End;
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Remove and return values from the head of a list for `LPOP`, from its tail
+/// for `RPOP`.
+///
+/// Without a count, a single value is returned. With one, an array of up to
+/// that many values is returned. Either way, nil is returned if the key does
+/// not exist.
+#[derive(Debug)]
+pub struct Pop {
+    /// Name of the key
+    key: String,
+
+    /// End of the list the values are popped from
+    end: End,
+
+    /// Number of values to pop, if given
+    count: Option<usize>,
+}
+
+impl Pop {
+    /// Create a new `Pop` command which pops `count` values, or a single one,
+    /// from the `end` of the list held by `key`.
+    pub fn new(key: impl ToString, end: End, count: Option<usize>) -> Pop {
+        Pop {
+            key: key.to_string(),
+            end,
+            count,
+        }
+    }
+
+    /// Parse a `Pop` instance from a received frame.
+    ///
+    /// The command name has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// LPOP key [count]
+    /// RPOP key [count]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse, end: End) -> Result<Pop, ParseError> {
+        let key = parse.next_string()?;
+
+        let count = match parse.remaining() {
+            0 => None,
+            _ => {
+                let count = usize::try_from(parse.next_int()?).map_err(|_| {
+                    ParseError::Other("value is out of range, must be positive".to_string())
+                })?;
+                Some(count)
+            }
+        };
+
+        Ok(Pop { key, end, count })
+    }
+
+    /// Apply the `Pop` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let count = self.count.unwrap_or(1);
+
+        let response = match db.pop(&self.key, self.end, count) {
+            Ok(Some(values)) if self.count.is_some() => {
+                Frame::Array(values.into_iter().map(Frame::Bulk).collect())
+            }
+            Ok(Some(values)) => values
+                .into_iter()
+                .next()
+                .map(Frame::Bulk)
+                .unwrap_or(Frame::Null),
+            // Like Redis, a count asks for an array, so the missing list is a
+            // null array rather than a null bulk string.
+            Ok(None) if self.count.is_some() => Frame::NullArray,
+            Ok(None) => Frame::Null,
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+
+    /// Returns the command name
+    pub fn get_name(&self) -> &str {
+        match self.end {
+            End::Left => "lpop",
+            End::Right => "rpop",
+        }
+    }
+}
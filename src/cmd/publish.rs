@@ -0,0 +1,61 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Posts a message to the given channel.
+///
+/// Send a message into a channel without any knowledge of individual
+/// consumers. Consumers may subscribe to channels in order to receive the
+/// messages.
+///
+/// Channel names have no relation to the key-value namespace. Publishing on a
+/// channel named "foo" has no relation to setting the "foo" key.
+#[derive(Debug)]
+pub struct Publish {
+    /// Name of the channel on which the message should be published.
+    channel: String,
+
+    /// The message to publish.
+    message: Bytes,
+}
+
+impl Publish {
+    /// Create a new `Publish` command which sends `message` on `channel`.
+    pub fn new(channel: impl ToString, message: Bytes) -> Publish {
+        Publish {
+            channel: channel.to_string(),
+            message,
+        }
+    }
+
+    /// Parse a `Publish` instance from a received frame.
+    ///
+    /// The `PUBLISH` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// PUBLISH channel message
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Publish, ParseError> {
+        let channel = parse.next_string()?;
+        let message = parse.next_bytes()?;
+
+        Ok(Publish { channel, message })
+    }
+
+    /// Apply the `Publish` command to the specified `Db` instance.
+    ///
+    /// The reply is the number of subscribers the message was sent to.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let num_subscribers = db.publish(&self.channel, self.message);
+
+        dst.queue_frame(&Frame::Integer(num_subscribers as i64))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Inspects the state of the Pub/Sub subsystem.
+///
+/// Only channels with subscribers are reported on. Pattern subscriptions are
+/// not taken into account by `CHANNELS` and `NUMSUB`.
+#[derive(Debug)]
+pub enum PubSub {
+    /// Lists the active channels, optionally only those matching a glob-style
+    /// pattern.
+    Channels(Option<String>),
+
+    /// Returns the number of subscribers of each of the given channels.
+    NumSub(Vec<String>),
+
+    /// Returns the number of patterns subscribed to.
+    NumPat,
+}
+
+impl PubSub {
+    /// Parse a `PubSub` instance from a received frame.
+    ///
+    /// The `PUBSUB` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// PUBSUB CHANNELS [pattern]
+    /// PUBSUB NUMSUB [channel [channel ...]]
+    /// PUBSUB NUMPAT
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<PubSub, ParseError> {
+        let subcommand = parse.next_string()?;
+
+        let pubsub = match &subcommand.to_lowercase()[..] {
+            "channels" if parse.remaining() <= 1 => {
+                let pattern = match parse.remaining() {
+                    0 => None,
+                    _ => Some(parse.next_string()?),
+                };
+                PubSub::Channels(pattern)
+            }
+            "numsub" => {
+                let mut channels = vec![];
+
+                while parse.remaining() > 0 {
+                    channels.push(parse.next_string()?);
+                }
+                PubSub::NumSub(channels)
+            }
+            "numpat" if parse.remaining() == 0 => PubSub::NumPat,
+            name @ ("channels" | "numpat") => {
+                return Err(ParseError::WrongArity(format!("pubsub|{}", name)));
+            }
+            _ => {
+                return Err(ParseError::Other(format!(
+                    "unknown subcommand '{}'. Try PUBSUB HELP.",
+                    subcommand
+                )))
+            }
+        };
+
+        Ok(pubsub)
+    }
+
+    /// Apply the `PubSub` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match self {
+            PubSub::Channels(pattern) => Frame::Array(
+                db.channels(pattern.as_deref())
+                    .into_iter()
+                    .map(|channel| Frame::Bulk(Bytes::from(channel)))
+                    .collect(),
+            ),
+            // RESP2 clients receive the map as a flat array of channels and
+            // counts, like Redis replies.
+            PubSub::NumSub(channels) => Frame::Map(
+                channels
+                    .into_iter()
+                    .map(|channel| {
+                        let count = db.num_subscribers(&channel) as i64;
+                        (Frame::Bulk(Bytes::from(channel)), Frame::Integer(count))
+                    })
+                    .collect(),
+            ),
+            PubSub::NumPat => Frame::Integer(db.num_patterns() as i64),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+
+    /// Returns the command name, including the subcommand
+    pub fn get_name(&self) -> &str {
+        match self {
+            PubSub::Channels(_) => "pubsub|channels",
+            PubSub::NumSub(_) => "pubsub|numsub",
+            PubSub::NumPat => "pubsub|numpat",
+        }
+    }
+}
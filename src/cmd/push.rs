@@ -0,0 +1,79 @@
+use crate::cmd::{Parse, ParseError};
+use crate::db::End;
+use crate::{Connection, Db, Frame};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Insert values at the head of a list for `LPUSH`, at its tail for `RPUSH`.
+///
+/// The values are pushed one after the other, so `LPUSH key a b c` results in
+/// a list starting with `c`. The list is created if the key does not exist.
+/// Returns the length of the list.
+#[derive(Debug)]
+pub struct Push {
+    /// Name of the key
+    key: String,
+
+    /// End of the list the values are pushed to
+    end: End,
+
+    /// Values to push
+    values: Vec<Bytes>,
+}
+
+impl Push {
+    /// Create a new `Push` command which pushes `values` to the `end` of the
+    /// list held by `key`.
+    pub fn new(key: impl ToString, end: End, values: Vec<Bytes>) -> Push {
+        Push {
+            key: key.to_string(),
+            end,
+            values,
+        }
+    }
+
+    /// Parse a `Push` instance from a received frame.
+    ///
+    /// The command name has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// LPUSH key element [element ...]
+    /// RPUSH key element [element ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse, end: End) -> Result<Push, ParseError> {
+        let key = parse.next_string()?;
+
+        // At least one value is required.
+        let mut values = vec![parse.next_bytes()?];
+
+        while parse.remaining() > 0 {
+            values.push(parse.next_bytes()?);
+        }
+
+        Ok(Push { key, end, values })
+    }
+
+    /// Apply the `Push` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.push(self.key, self.end, self.values) {
+            Ok(len) => Frame::Integer(len as i64),
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+
+    /// Returns the command name
+    pub fn get_name(&self) -> &str {
+        match self.end {
+            End::Left => "lpush",
+            End::Right => "rpush",
+        }
+    }
+}
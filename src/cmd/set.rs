@@ -0,0 +1,112 @@
+use crate::cmd::{parse_expire_option, Parse, ParseError};
+use crate::db::{Condition, Expiry};
+use crate::{Connection, Db, Frame};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Set `key` to hold the string `value`.
+///
+/// If `key` already holds a value, it is overwritten, and any previous time
+/// to live associated with the key is discarded, unless `KEEPTTL` is given.
+#[derive(Debug)]
+pub struct Set {
+    /// the lookup key
+    key: String,
+
+    /// the value to be stored
+    value: Bytes,
+
+    /// What happens to the key's expiration
+    expiry: Expiry,
+
+    /// Whether the key must exist, or not, to be set
+    condition: Condition,
+
+    /// Whether the previous value is returned instead of `OK`
+    get: bool,
+}
+
+impl Set {
+    /// Create a new `Set` command which sets `key` to `value`, with no
+    /// expiration.
+    pub fn new(key: impl ToString, value: Bytes) -> Set {
+        Set {
+            key: key.to_string(),
+            value,
+            expiry: Expiry::Persist,
+            condition: Condition::Always,
+            get: false,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Get the value
+    pub fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    /// Get the expiration given to the key
+    pub fn expiry(&self) -> Expiry {
+        self.expiry
+    }
+
+    /// Get the condition for the key to be set
+    pub fn condition(&self) -> Condition {
+        self.condition
+    }
+
+    /// Parse a `Set` instance from a received frame.
+    ///
+    /// The `SET` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// SET key value [NX | XX] [GET] [EX seconds | PX milliseconds |
+    ///   EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Set, ParseError> {
+        let mut set = Set::new(parse.next_string()?, parse.next_bytes()?);
+        let mut expiry = None;
+
+        // Options may be given in any order, but each at most once and
+        // without contradicting another.
+        while parse.remaining() > 0 {
+            let option = parse.next_string()?.to_lowercase();
+
+            match option.as_str() {
+                "nx" if set.condition == Condition::Always => set.condition = Condition::IfAbsent,
+                "xx" if set.condition == Condition::Always => set.condition = Condition::IfPresent,
+                "get" => set.get = true,
+                "keepttl" if expiry.is_none() => expiry = Some(Expiry::Keep),
+                "ex" | "px" | "exat" | "pxat" if expiry.is_none() => {
+                    expiry = Some(Expiry::At(parse_expire_option(parse, &option)?));
+                }
+                _ => return Err(ParseError::Syntax),
+            }
+        }
+
+        set.expiry = expiry.unwrap_or(Expiry::Persist);
+        Ok(set)
+    }
+
+    /// Apply the `Set` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let response = match db.set(self.key, self.value, self.expiry, self.condition, self.get) {
+            Ok((_, previous)) if self.get => previous.map(Frame::Bulk).unwrap_or(Frame::Null),
+            Ok((true, _)) => Frame::Simple("OK".to_string()),
+            Ok((false, _)) => Frame::Null,
+            Err(err) => err.to_frame(),
+        };
+
+        dst.queue_frame(&response).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,568 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Command, Connection, Db, Frame, Shutdown, Timeouts};
+use bytes::Bytes;
+use std::pin::Pin;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::select;
+use tokio::sync::broadcast;
+use tokio_stream::{Stream, StreamExt, StreamMap};
+
+/// Subscribes the client to one or more channels.
+///
+/// Once the client enters the subscribed state, it is not supposed to issue
+/// any other commands, except for additional (P)SUBSCRIBE and (P)UNSUBSCRIBE
+/// commands, and `PING`. It leaves the subscribed state once it has
+/// unsubscribed from every channel and pattern.
+#[derive(Debug)]
+pub struct Subscribe {
+    channels: Vec<String>,
+}
+
+/// Unsubscribes the client from one or more channels.
+///
+/// When no channels are specified, the client is unsubscribed from all the
+/// previously subscribed channels.
+#[derive(Clone, Debug)]
+pub struct Unsubscribe {
+    channels: Vec<String>,
+}
+
+/// Subscribes the client to the channels matching one or more glob-style
+/// patterns, as described in the `glob` module.
+///
+/// Like `SUBSCRIBE`, this makes the connection enter subscriber mode.
+#[derive(Debug)]
+pub struct PSubscribe {
+    patterns: Vec<String>,
+}
+
+/// Unsubscribes the client from one or more patterns.
+///
+/// When no patterns are specified, the client is unsubscribed from all the
+/// previously subscribed patterns.
+#[derive(Clone, Debug)]
+pub struct PUnsubscribe {
+    patterns: Vec<String>,
+}
+
+/// A channel or a pattern a client subscribed to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Subscription {
+    Channel(String),
+    Pattern(String),
+}
+
+/// Stream of messages. The stream receives messages from the
+/// `broadcast::Receiver`, and yields the frames to send to the client, or the
+/// number of messages dropped because the client did not keep up. We use
+/// `stream!` to create a `Stream` that consumes messages. Because `stream!`
+/// values cannot be named, we box the stream using a trait object.
+type Messages = Pin<Box<dyn Stream<Item = Result<Frame, u64>> + Send>>;
+
+impl Subscribe {
+    /// Creates a new `Subscribe` command to listen on the specified channels.
+    pub fn new(channels: &[String]) -> Subscribe {
+        Subscribe {
+            channels: channels.to_vec(),
+        }
+    }
+
+    /// Parse a `Subscribe` instance from a received frame.
+    ///
+    /// The `SUBSCRIBE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing two or more entries.
+    ///
+    /// ```text
+    /// SUBSCRIBE channel [channel ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Subscribe, ParseError> {
+        // At least one channel is required.
+        let mut channels = vec![parse.next_string()?];
+
+        while parse.remaining() > 0 {
+            channels.push(parse.next_string()?);
+        }
+
+        Ok(Subscribe { channels })
+    }
+
+    /// Apply the `Subscribe` command to the specified `Db` instance.
+    ///
+    /// The connection enters subscriber mode: it receives the messages
+    /// published on its channels, and further (P)SUBSCRIBE and (P)UNSUBSCRIBE
+    /// commands are handled here. This returns once the client has
+    /// unsubscribed from every channel and pattern, has disconnected, or the
+    /// server is shutting down.
+    pub(crate) async fn apply<S>(
+        self,
+        db: &Db,
+        dst: &mut Connection<S>,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let channels = self.channels.into_iter().map(Subscription::Channel);
+        subscriber_mode(channels.collect(), db, dst, shutdown).await
+    }
+}
+
+impl PSubscribe {
+    /// Creates a new `PSubscribe` command to listen on the channels matching
+    /// the specified patterns.
+    pub fn new(patterns: &[String]) -> PSubscribe {
+        PSubscribe {
+            patterns: patterns.to_vec(),
+        }
+    }
+
+    /// Parse a `PSubscribe` instance from a received frame.
+    ///
+    /// The `PSUBSCRIBE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// PSUBSCRIBE pattern [pattern ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<PSubscribe, ParseError> {
+        let Subscribe { channels } = Subscribe::parse_frames(parse)?;
+
+        Ok(PSubscribe { patterns: channels })
+    }
+
+    /// Apply the `PSubscribe` command to the specified `Db` instance, entering
+    /// subscriber mode like `Subscribe::apply`.
+    pub(crate) async fn apply<S>(
+        self,
+        db: &Db,
+        dst: &mut Connection<S>,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let patterns = self.patterns.into_iter().map(Subscription::Pattern);
+        subscriber_mode(patterns.collect(), db, dst, shutdown).await
+    }
+}
+
+/// Runs the connection in subscriber mode, starting with the `subscribe_to`
+/// subscriptions.
+async fn subscriber_mode<S>(
+    subscribe_to: Vec<Subscription>,
+    db: &Db,
+    dst: &mut Connection<S>,
+    shutdown: &mut Shutdown,
+) -> crate::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Like Redis, subscribers are not disconnected for being idle: waiting
+    // for messages is what they do.
+    let timeouts = *dst.timeouts();
+    dst.set_timeouts(Timeouts {
+        idle: None,
+        ..timeouts
+    });
+
+    let res = run(subscribe_to, db, dst, shutdown).await;
+
+    dst.set_timeouts(timeouts);
+    res
+}
+
+async fn run<S>(
+    mut subscribe_to: Vec<Subscription>,
+    db: &Db,
+    dst: &mut Connection<S>,
+    shutdown: &mut Shutdown,
+) -> crate::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Each individual channel or pattern subscription is handled using a
+    // `broadcast::Receiver`. Messages are then fanned out to all clients
+    // subscribed to the channels.
+    //
+    // An individual client may subscribe to multiple channels and may
+    // dynamically add and remove channels from its subscription set. To
+    // handle this, a `StreamMap` is used to track active subscriptions.
+    let mut subscriptions = StreamMap::new();
+
+    loop {
+        // `subscribe_to` is used to track additional channels and patterns to
+        // subscribe to. When new (P)SUBSCRIBE commands are received, the new
+        // subscriptions are pushed onto this vec.
+        for subscription in subscribe_to.drain(..) {
+            subscribe(subscription, &mut subscriptions, db, dst).await?;
+        }
+
+        if subscriptions.is_empty() {
+            return Ok(());
+        }
+
+        // Wait for one of the following to happen:
+        //
+        // - Receive a message from one of the subscribed channels.
+        // - Receive a subscribe or unsubscribe command from the client.
+        // - A server shutdown signal.
+        select! {
+            // Receive messages from subscribed channels
+            Some((_, msg)) = subscriptions.next() => match msg {
+                Ok(msg) => dst.write_frame(&msg).await?,
+                Err(skipped) => return lagged(skipped, dst).await,
+            },
+            res = dst.read_frame() => {
+                let frame = match res? {
+                    Some(frame) => frame,
+                    // This happens if the remote client has disconnected.
+                    None => return Ok(())
+                };
+
+                handle_command(
+                    frame,
+                    &mut subscribe_to,
+                    &mut subscriptions,
+                    dst,
+                ).await?;
+            }
+            _ = shutdown.recv() => {
+                return Ok(());
+            }
+        };
+    }
+}
+
+/// Ends subscriber mode for a client that did not keep up with the messages,
+/// `skipped` of which were dropped.
+///
+/// Like Redis disconnecting subscribers over their output buffer limit, the
+/// client is told why, then the connection is closed, rather than going on
+/// without the messages it missed.
+async fn lagged<S>(skipped: u64, dst: &mut Connection<S>) -> crate::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let err = format!("ERR subscriber too slow, {} messages were dropped", skipped);
+
+    dst.write_frame(&Frame::Error(err.clone())).await?;
+    Err(err.into())
+}
+
+async fn subscribe<S>(
+    subscription: Subscription,
+    subscriptions: &mut StreamMap<Subscription, Messages>,
+    db: &Db,
+    dst: &mut Connection<S>,
+) -> crate::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Subscribing twice to the same channel or pattern is a no-op, apart from
+    // the reply.
+    if !subscriptions.contains_key(&subscription) {
+        let rx: Messages = match &subscription {
+            Subscription::Channel(channel) => {
+                let mut rx = db.subscribe(channel.clone());
+                let channel = channel.clone();
+
+                Box::pin(async_stream::stream! {
+                    loop {
+                        match rx.recv().await {
+                            Ok(msg) => yield Ok(make_message_frame(&channel, msg)),
+                            // The client did not keep up and the oldest
+                            // messages were dropped.
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => yield Err(skipped),
+                            Err(_) => break,
+                        }
+                    }
+                })
+            }
+            Subscription::Pattern(pattern) => {
+                let mut rx = db.psubscribe(pattern.clone());
+                let pattern = pattern.clone();
+
+                Box::pin(async_stream::stream! {
+                    loop {
+                        match rx.recv().await {
+                            Ok((channel, msg)) => {
+                                yield Ok(make_pmessage_frame(&pattern, channel, msg))
+                            }
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => yield Err(skipped),
+                            Err(_) => break,
+                        }
+                    }
+                })
+            }
+        };
+
+        // Track subscription in this client's subscription set.
+        subscriptions.insert(subscription.clone(), rx);
+    }
+
+    // Respond with the successful subscription
+    let response = make_subscribe_frame(subscription, subscriptions.len());
+    dst.queue_frame(&response).await?;
+
+    Ok(())
+}
+
+/// Handle a command received while in subscriber mode. Only subscribe and
+/// unsubscribe commands, and `PING`, are permitted in this context.
+///
+/// Any new subscriptions are appended to `subscribe_to` instead of modifying
+/// `subscriptions`.
+async fn handle_command<S>(
+    frame: Frame,
+    subscribe_to: &mut Vec<Subscription>,
+    subscriptions: &mut StreamMap<Subscription, Messages>,
+    dst: &mut Connection<S>,
+) -> crate::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // A command has been received from the client.
+    //
+    // Only (P)SUBSCRIBE, (P)UNSUBSCRIBE and PING commands are permitted in
+    // this context.
+    let (kind, unsubscribe_from): (&'static [u8], Vec<Subscription>) = match Command::from_frame(
+        frame,
+    ) {
+        Ok(Command::Subscribe(subscribe)) => {
+            // This will be handled by the outer loop.
+            let channels = subscribe.channels.into_iter();
+            subscribe_to.extend(channels.map(Subscription::Channel));
+            return Ok(());
+        }
+        Ok(Command::PSubscribe(psubscribe)) => {
+            let patterns = psubscribe.patterns.into_iter();
+            subscribe_to.extend(patterns.map(Subscription::Pattern));
+            return Ok(());
+        }
+        // If no channels are specified, this requests unsubscribing from
+        // **all** channels. To implement this, the channels currently
+        // subscribed to are listed.
+        Ok(Command::Unsubscribe(unsubscribe)) if unsubscribe.channels.is_empty() => {
+            let channels = subscriptions
+                .keys()
+                .filter(|subscription| matches!(subscription, Subscription::Channel(_)));
+            (b"unsubscribe", channels.cloned().collect())
+        }
+        Ok(Command::Unsubscribe(unsubscribe)) => {
+            let channels = unsubscribe.channels.into_iter();
+            (
+                b"unsubscribe",
+                channels.map(Subscription::Channel).collect(),
+            )
+        }
+        Ok(Command::PUnsubscribe(punsubscribe)) if punsubscribe.patterns.is_empty() => {
+            let patterns = subscriptions
+                .keys()
+                .filter(|subscription| matches!(subscription, Subscription::Pattern(_)));
+            (b"punsubscribe", patterns.cloned().collect())
+        }
+        Ok(Command::PUnsubscribe(punsubscribe)) => {
+            let patterns = punsubscribe.patterns.into_iter();
+            (
+                b"punsubscribe",
+                patterns.map(Subscription::Pattern).collect(),
+            )
+        }
+        Ok(Command::Ping(ping)) => {
+            ping.apply_subscribed(dst).await?;
+            return Ok(());
+        }
+        Ok(command) => {
+            let response = Frame::Error(format!(
+                "ERR Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING are allowed in this context",
+                command.get_name()
+            ));
+            dst.queue_frame(&response).await?;
+            return Ok(());
+        }
+        Err(err) => {
+            dst.queue_frame(&err.to_frame()).await?;
+            return Ok(());
+        }
+    };
+
+    // Unsubscribing from all the channels while only subscribed to patterns,
+    // or the other way around, still gets a reply.
+    if unsubscribe_from.is_empty() {
+        let response = make_unsubscribe_none_frame(kind, subscriptions.len());
+        dst.queue_frame(&response).await?;
+    }
+
+    for subscription in unsubscribe_from {
+        subscriptions.remove(&subscription);
+
+        let response = make_unsubscribe_frame(subscription, subscriptions.len());
+        dst.queue_frame(&response).await?;
+    }
+
+    Ok(())
+}
+
+/// Creates the response to a subscribe request.
+///
+/// Like every frame sent in subscriber mode, it is a push, which is sent as
+/// an array to RESP2 clients.
+fn make_subscribe_frame(subscription: Subscription, num_subs: usize) -> Frame {
+    let (kind, name): (&'static [u8], _) = match subscription {
+        Subscription::Channel(channel) => (b"subscribe", channel),
+        Subscription::Pattern(pattern) => (b"psubscribe", pattern),
+    };
+
+    Frame::Push(vec![
+        Frame::Bulk(Bytes::from_static(kind)),
+        Frame::Bulk(Bytes::from(name)),
+        Frame::Integer(num_subs as i64),
+    ])
+}
+
+/// Creates the response to an unsubscribe request.
+fn make_unsubscribe_frame(subscription: Subscription, num_subs: usize) -> Frame {
+    let (kind, name): (&'static [u8], _) = match subscription {
+        Subscription::Channel(channel) => (b"unsubscribe", channel),
+        Subscription::Pattern(pattern) => (b"punsubscribe", pattern),
+    };
+
+    Frame::Push(vec![
+        Frame::Bulk(Bytes::from_static(kind)),
+        Frame::Bulk(Bytes::from(name)),
+        Frame::Integer(num_subs as i64),
+    ])
+}
+
+/// Creates the response to unsubscribing from all the channels or patterns
+/// while not subscribed to any. `kind` is either `unsubscribe` or
+/// `punsubscribe`, and `num_subs` the number of remaining subscriptions of
+/// the other kind.
+fn make_unsubscribe_none_frame(kind: &'static [u8], num_subs: usize) -> Frame {
+    Frame::Push(vec![
+        Frame::Bulk(Bytes::from_static(kind)),
+        Frame::Null,
+        Frame::Integer(num_subs as i64),
+    ])
+}
+
+/// Creates a message informing the client about a new message on a channel
+/// that the client subscribes to.
+fn make_message_frame(channel_name: &str, msg: Bytes) -> Frame {
+    Frame::Push(vec![
+        Frame::Bulk(Bytes::from_static(b"message")),
+        Frame::Bulk(Bytes::copy_from_slice(channel_name.as_bytes())),
+        Frame::Bulk(msg),
+    ])
+}
+
+/// Creates a message informing the client about a new message on a channel
+/// matching a pattern that the client subscribes to.
+fn make_pmessage_frame(pattern: &str, channel_name: String, msg: Bytes) -> Frame {
+    Frame::Push(vec![
+        Frame::Bulk(Bytes::from_static(b"pmessage")),
+        Frame::Bulk(Bytes::copy_from_slice(pattern.as_bytes())),
+        Frame::Bulk(Bytes::from(channel_name)),
+        Frame::Bulk(msg),
+    ])
+}
+
+impl Unsubscribe {
+    /// Create a new `Unsubscribe` command with the given `channels`.
+    pub fn new(channels: &[String]) -> Unsubscribe {
+        Unsubscribe {
+            channels: channels.to_vec(),
+        }
+    }
+
+    /// Parse an `Unsubscribe` instance from a received frame.
+    ///
+    /// The `UNSUBSCRIBE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// Expects an array frame containing at least one entry.
+    ///
+    /// ```text
+    /// UNSUBSCRIBE [channel [channel ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Unsubscribe, ParseError> {
+        let mut channels = vec![];
+
+        while parse.remaining() > 0 {
+            channels.push(parse.next_string()?);
+        }
+
+        Ok(Unsubscribe { channels })
+    }
+
+    /// Apply the `Unsubscribe` command outside of subscriber mode.
+    ///
+    /// The client is not subscribed to any channel, so like Redis, the reply
+    /// only confirms that it is subscribed to none.
+    pub(crate) async fn apply<S>(self, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        if self.channels.is_empty() {
+            let response = make_unsubscribe_none_frame(b"unsubscribe", 0);
+            dst.queue_frame(&response).await?;
+        }
+
+        for channel_name in self.channels {
+            let subscription = Subscription::Channel(channel_name);
+            dst.queue_frame(&make_unsubscribe_frame(subscription, 0))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PUnsubscribe {
+    /// Create a new `PUnsubscribe` command with the given `patterns`.
+    pub fn new(patterns: &[String]) -> PUnsubscribe {
+        PUnsubscribe {
+            patterns: patterns.to_vec(),
+        }
+    }
+
+    /// Parse a `PUnsubscribe` instance from a received frame.
+    ///
+    /// The `PUNSUBSCRIBE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// PUNSUBSCRIBE [pattern [pattern ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<PUnsubscribe, ParseError> {
+        let Unsubscribe { channels } = Unsubscribe::parse_frames(parse)?;
+
+        Ok(PUnsubscribe { patterns: channels })
+    }
+
+    /// Apply the `PUnsubscribe` command outside of subscriber mode, like
+    /// `Unsubscribe::apply`.
+    pub(crate) async fn apply<S>(self, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        if self.patterns.is_empty() {
+            let response = make_unsubscribe_none_frame(b"punsubscribe", 0);
+            dst.queue_frame(&response).await?;
+        }
+
+        for pattern in self.patterns {
+            let subscription = Subscription::Pattern(pattern);
+            dst.queue_frame(&make_unsubscribe_frame(subscription, 0))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
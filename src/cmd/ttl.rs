@@ -0,0 +1,76 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Get the remaining time to live of a key, for `TTL` in seconds and for
+/// `PTTL` in milliseconds.
+///
+/// `-2` is returned if the key does not exist, `-1` if it has no expiration.
+#[derive(Debug)]
+pub struct Ttl {
+    /// Name of the key
+    key: String,
+
+    /// Whether the time is returned in milliseconds
+    millis: bool,
+}
+
+impl Ttl {
+    /// Create a new `Ttl` command, returning the time to live of `key` in
+    /// milliseconds if `millis` is set, seconds otherwise.
+    pub fn new(key: impl ToString, millis: bool) -> Ttl {
+        Ttl {
+            key: key.to_string(),
+            millis,
+        }
+    }
+
+    /// Parse a `Ttl` instance from a received frame.
+    ///
+    /// The command name has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// TTL key
+    /// PTTL key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse, millis: bool) -> Result<Ttl, ParseError> {
+        let key = parse.next_string()?;
+
+        Ok(Ttl { key, millis })
+    }
+
+    /// Returns the command name
+    pub fn get_name(&self) -> &str {
+        if self.millis {
+            "pttl"
+        } else {
+            "ttl"
+        }
+    }
+
+    /// Apply the `Ttl` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let ttl = match db.ttl(&self.key) {
+            None => -2,
+            Some(None) => -1,
+            Some(Some(ttl)) => {
+                let millis = ttl.as_millis() as i64;
+
+                // Like Redis, seconds are rounded to the nearest.
+                if self.millis {
+                    millis
+                } else {
+                    (millis + 500) / 1000
+                }
+            }
+        };
+
+        dst.queue_frame(&Frame::Integer(ttl)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use crate::cmd::{Parse, ParseError};
+use crate::{Connection, Db, Frame};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Get the kind of value stored at key.
+///
+/// The reply is one of `string`, `list`, `hash`, `set` and `zset`, or `none`
+/// if the key does not exist.
+#[derive(Debug)]
+pub struct Type {
+    /// Name of the key
+    key: String,
+}
+
+impl Type {
+    /// Create a new `Type` command which returns the kind of value of `key`.
+    pub fn new(key: impl ToString) -> Type {
+        Type {
+            key: key.to_string(),
+        }
+    }
+
+    /// Parse a `Type` instance from a received frame.
+    ///
+    /// The `TYPE` string has already been consumed.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// TYPE key
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Result<Type, ParseError> {
+        let key = parse.next_string()?;
+
+        Ok(Type { key })
+    }
+
+    /// Apply the `Type` command to the specified `Db` instance.
+    pub(crate) async fn apply<S>(self, db: &Db, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let type_name = db.value_type(&self.key).unwrap_or("none");
+
+        dst.queue_frame(&Frame::Simple(type_name.to_string()))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use crate::cmd::Parse;
+use crate::{Connection, Frame};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// Number of bytes of the name, and of the arguments, of an unknown command
+/// quoted in the error reply at most.
+const MAX_QUOTED: usize = 128;
+
+/// Represents an "unknown" command. This is not a real `Redis` command.
+#[derive(Debug)]
+pub struct Unknown {
+    command_name: String,
+
+    /// The arguments the command was given, quoted in the error reply.
+    args: Vec<Bytes>,
+}
+
+impl Unknown {
+    /// Create a new `Unknown` command which responds to unknown commands
+    /// issued by clients
+    pub(crate) fn parse_frames(parse: &mut Parse) -> Unknown {
+        let mut args = vec![];
+
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+
+        Unknown {
+            command_name: parse.name().to_string(),
+            args,
+        }
+    }
+
+    /// Returns the command name
+    pub fn get_name(&self) -> &str {
+        &self.command_name
+    }
+
+    /// Responds to the client, indicating the command is not recognized.
+    pub(crate) async fn apply<S>(self, dst: &mut Connection<S>) -> crate::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        // Like Redis, the name and arguments quoted are truncated, so that a
+        // client cannot make the reply arbitrarily long.
+        let name = &self.command_name.as_bytes()[..self.command_name.len().min(MAX_QUOTED)];
+        let mut args = vec![];
+
+        for arg in &self.args {
+            if args.len() >= MAX_QUOTED {
+                break;
+            }
+
+            let len = arg.len().min(MAX_QUOTED - args.len());
+            args.push(b'\'');
+            args.extend_from_slice(&arg[..len]);
+            args.extend_from_slice(b"' ");
+        }
+
+        let response = format!(
+            "ERR unknown command '{}', with args beginning with: {}",
+            String::from_utf8_lossy(name),
+            String::from_utf8_lossy(&args)
+        );
+
+        dst.queue_frame(&Frame::Error(response)).await?;
+        Ok(())
+    }
+}
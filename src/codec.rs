@@ -0,0 +1,102 @@
+use crate::frame::{Frame, Protocol, ProtocolLimits};
+use crate::parser::Parser;
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A `tokio_util` codec reading and writing Redis protocol frames.
+///
+/// Decoding is built on the incremental `Parser`, and encoding on
+/// `Frame::encode`, so a `Framed` transport behaves exactly like a
+/// `Connection`. This allows frames to be used with any transport through
+/// `Framed`, `FramedRead` and `FramedWrite`, and plugged into `Stream` and
+/// `Sink` pipelines.
+///
+/// Malformed input is reported as a `ProtocolError`, boxed into the crate's
+/// `Error` type.
+///
+/// The codec keeps the same per-connection state as a `Connection`: the
+/// protocol version used to encode frames and the limits enforced on decoded
+/// frames. When used with `Framed`, they can be updated through
+/// `Framed::codec_mut`, e.g. in response to a `HELLO` command.
+#[derive(Debug, Default)]
+pub struct RespCodec {
+    protocol: Protocol,
+    parser: Parser,
+}
+
+impl RespCodec {
+    /// Returns a codec speaking RESP2 with the default limits.
+    pub fn new() -> RespCodec {
+        RespCodec::default()
+    }
+
+    /// Returns the protocol version used to encode frames.
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Switches the protocol version used to encode frames.
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.protocol = protocol;
+    }
+
+    /// Returns the limits enforced on decoded frames.
+    pub fn limits(&self) -> &ProtocolLimits {
+        self.parser.limits()
+    }
+
+    /// Sets the limits enforced on decoded frames.
+    pub fn set_limits(&mut self, limits: ProtocolLimits) {
+        self.parser.set_limits(limits);
+    }
+
+    /// Returns `true` if part of a frame has been decoded but the frame is
+    /// not complete yet.
+    pub fn has_partial_frame(&self) -> bool {
+        self.parser.has_partial_frame()
+    }
+}
+
+impl Decoder for RespCodec {
+    type Item = Frame;
+    type Error = crate::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> crate::Result<Option<Frame>> {
+        // The parser consumes what it has parsed from `src` and keeps track
+        // of partially received frames, so a large frame is not scanned again
+        // every time more of it is received.
+        Ok(self.parser.parse(src)?)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> crate::Result<Option<Frame>> {
+        if let Some(frame) = self.decode(src)? {
+            return Ok(Some(frame));
+        }
+
+        // For the stream to end cleanly, there should be no data left in the
+        // buffer nor any partially parsed frame. If there is, this means that
+        // the peer closed the socket while sending a frame.
+        match self.parser.eof(src.len()) {
+            Some(err) => Err(err.into()),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder<Frame> for RespCodec {
+    type Error = crate::Error;
+
+    fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> crate::Result<()> {
+        item.encode(dst, self.protocol);
+        Ok(())
+    }
+}
+
+impl Encoder<&Frame> for RespCodec {
+    type Error = crate::Error;
+
+    fn encode(&mut self, item: &Frame, dst: &mut BytesMut) -> crate::Result<()> {
+        item.encode(dst, self.protocol);
+        Ok(())
+    }
+}
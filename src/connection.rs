@@ -1,131 +1,359 @@
-use std::io::Cursor;
-
-use crate::{frame, Frame, Result};
-use bytes::{Buf, BytesMut};
-use tokio::io::{self, AsyncReadExt, AsyncWriteExt, BufWriter};
+use crate::codec::RespCodec;
+use crate::frame::{Frame, Protocol, ProtocolLimits};
+use crate::Result;
+use bytes::BytesMut;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tokio::net::TcpStream;
+use tokio::time::{self, Instant};
+use tokio_util::codec::Decoder;
 
+/// Sends and receives frames over a byte stream.
+///
+/// The stream defaults to a `TcpStream`, but any transport implementing
+/// `AsyncRead` and `AsyncWrite` can be used: a `UnixStream`, a TLS stream, one
+/// end of a `tokio::io::duplex` pipe in tests, or stdin and stdout put
+/// together with `join`.
+///
+/// Frames can be queued with `queue_frame` rather than written right away.
+/// Queued frames are sent together, in as few writes as possible, once the
+/// connection has no more complete frames to read or enough of them have been
+/// queued. This is what makes pipelining fast: a client sending a thousand
+/// commands at once gets the replies back in a handful of writes rather than
+/// a thousand.
+///
+/// Reads and writes can be bounded in time with `set_timeouts`, so a peer
+/// that went away without closing the connection, or that stops reading
+/// replies, does not hold on to it forever.
 #[derive(Debug)]
-pub struct Connection {
-    stream: BufWriter<TcpStream>,
+pub struct Connection<S = TcpStream> {
+    stream: S,
     buffer: BytesMut,
+
+    /// Encoded frames that have not been written to the stream yet.
+    pending: BytesMut,
+
+    /// Number of pending bytes above which they are written without waiting.
+    flush_threshold: usize,
+
+    timeouts: Timeouts,
+
+    /// When the frame being read started to be waited for, if one is
+    /// partially received.
+    frame_started: Option<Instant>,
+
+    codec: RespCodec,
 }
 
-impl Connection {
-    pub fn new(stream: TcpStream) -> Connection {
+/// Time limits applied to a `Connection`. `None` means no limit, which is the
+/// default.
+///
+/// A connection exceeding one of them fails with an `io::Error` of kind
+/// `TimedOut`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// How long the peer may stay silent between two frames.
+    pub idle: Option<Duration>,
+
+    /// How long the peer may take to send the rest of a frame once it has
+    /// started sending it.
+    pub read: Option<Duration>,
+
+    /// How long writing replies may take, for peers that do not read them
+    /// fast enough.
+    pub write: Option<Duration>,
+}
+
+/// Default number of pending bytes above which queued frames are written.
+/// This is the size of the chunks Redis writes replies in.
+const DEFAULT_FLUSH_THRESHOLD: usize = 16 * 1024;
+
+/// Number of bytes `closed` buffers at most while waiting for the peer to go
+/// away.
+const MAX_BACKLOG: usize = 64 * 1024;
+
+/// A stream reading from one half and writing to the other.
+///
+/// Returned by `join`.
+#[derive(Debug)]
+pub struct Join<R, W> {
+    reader: R,
+    writer: W,
+}
+
+/// Joins a reader and a writer into a single stream, for instance stdin and
+/// stdout, so they can be used by a `Connection`.
+pub fn join<R, W>(reader: R, writer: W) -> Join<R, W>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    Join { reader, writer }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    pub fn new(stream: S) -> Connection<S> {
         Connection {
-            stream: BufWriter::new(stream),
+            stream,
             buffer: BytesMut::with_capacity(4096),
+            pending: BytesMut::new(),
+            flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+            timeouts: Timeouts::default(),
+            frame_started: None,
+            codec: RespCodec::new(),
         }
     }
 
+    /// Returns the protocol version used to encode frames written to the
+    /// connection.
+    pub fn protocol(&self) -> Protocol {
+        self.codec.protocol()
+    }
+
+    /// Switches the protocol version used to encode frames written to the
+    /// connection. This is done in response to a `HELLO` command.
+    pub fn set_protocol(&mut self, protocol: Protocol) {
+        self.codec.set_protocol(protocol);
+    }
+
+    /// Returns the limits enforced on frames read from the connection.
+    pub fn limits(&self) -> &ProtocolLimits {
+        self.codec.limits()
+    }
+
+    /// Sets the limits enforced on frames read from the connection. Frames
+    /// exceeding them make `read_frame` return a protocol error.
+    pub fn set_limits(&mut self, limits: ProtocolLimits) {
+        self.codec.set_limits(limits);
+    }
+
+    /// Returns the time limits applied to the connection.
+    pub fn timeouts(&self) -> &Timeouts {
+        &self.timeouts
+    }
+
+    /// Sets the time limits applied to the connection.
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
+    /// Returns the number of queued bytes above which `queue_frame` writes
+    /// them to the stream.
+    pub fn flush_threshold(&self) -> usize {
+        self.flush_threshold
+    }
+
+    /// Sets the number of queued bytes above which `queue_frame` writes them
+    /// to the stream. With `0`, every frame is written as soon as it is
+    /// queued.
+    pub fn set_flush_threshold(&mut self, bytes: usize) {
+        self.flush_threshold = bytes;
+    }
+
     /// Read a frame from the connection.
     ///
     /// Returns `None` if EOF is reached.
+    ///
+    /// Queued frames are flushed before waiting for more data: the peer may
+    /// be waiting for them before sending anything else.
+    ///
+    /// If the peer sends malformed data, or closes the connection in the
+    /// middle of a frame, the returned error is a `ProtocolError`. It can be
+    /// recovered with `downcast_ref` to inspect where the problem happened or
+    /// reply to the peer with `ProtocolError::to_frame`.
     pub async fn read_frame(&mut self) -> Result<Option<Frame>> {
         loop {
             // Attempt to parse a frame from the buffered data. If enough data
             // has been buffered, the frame is returned.
-            if let Some(frame) = self.parse_frame()? {
+            if let Some(frame) = self.codec.decode(&mut self.buffer)? {
+                self.frame_started = None;
                 return Ok(Some(frame));
             }
 
-            // There is not enough buffered data to read a frame. Attempt to
-            // read more data from the socket.
-            //
+            // There is not enough buffered data to read a frame. Send the
+            // queued replies to the frames read so far, then attempt to read
+            // more data from the socket.
+            self.flush().await?;
+
+            // Between two frames, the peer may stay idle for as long as the
+            // idle timeout allows. Once a frame has started, the rest of it
+            // must arrive before the read timeout.
+            let (timeout, kind) = if self.buffer.is_empty() && !self.codec.has_partial_frame() {
+                self.frame_started = None;
+                (self.timeouts.idle, "idle")
+            } else {
+                let started = *self.frame_started.get_or_insert_with(Instant::now);
+                let deadline = self.timeouts.read.map(|read| started + read);
+
+                (
+                    deadline.map(|deadline| deadline.saturating_duration_since(Instant::now())),
+                    "read",
+                )
+            };
+
             // On success, the number of bytes is returned. `0` indicates
             // "end of stream".
-            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+            let read = self.stream.read_buf(&mut self.buffer);
+
+            if 0 == with_timeout(timeout, kind, read).await? {
                 // The remote closed the connection. For this to be a clean
-                // shutdown, there should be no data in the read buffer. If
-                // there is, this means that the peer closed the socket while
-                // sending a frame.
-                if self.buffer.is_empty() {
-                    return Ok(None);
-                } else {
-                    return Err("connection reset by peer".into());
-                }
+                // shutdown, there should be no data in the read buffer nor a
+                // partially parsed frame. If there is, this means that the
+                // peer closed the socket while sending a frame.
+                return self.codec.decode_eof(&mut self.buffer);
             }
         }
     }
 
-    /// Write a decimal frame to the stream.
-    async fn write_decimal(&mut self, val: i64) -> io::Result<()> {
-        use std::io::Write;
-
-        // Convert the value to a string.
-        let mut buf = [0u8; 12];
-        let mut buf = Cursor::new(&mut buf[..]);
-        write!(&mut buf, "{}", val)?;
+    /// Waits until the peer closes the connection, like
+    /// `oneshot::Sender::closed` does for a channel.
+    ///
+    /// This is meant for when the peer is waiting for a reply, e.g. to a
+    /// blocking command, so that the server notices a peer going away. Queued
+    /// frames are flushed first. Data the peer sends in the meantime is kept,
+    /// to be returned by the next calls to `read_frame`. No timeout applies.
+    ///
+    /// Fails if the peer sends more than `MAX_BACKLOG` bytes in the meantime.
+    pub async fn closed(&mut self) -> io::Result<()> {
+        self.flush().await?;
 
-        let pos = buf.position() as usize;
-        self.stream.write_all(&buf.get_ref()[..pos]).await?;
-        self.stream.write_all(b"\r\n").await?;
+        loop {
+            // Like Redis closing clients going over their query buffer limit,
+            // a peer is not allowed to pile up an unbounded amount of data.
+            if self.buffer.len() >= MAX_BACKLOG {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "too much data sent while waiting for a reply",
+                ));
+            }
 
-        Ok(())
+            if 0 == self.stream.read_buf(&mut self.buffer).await? {
+                return Ok(());
+            }
+        }
     }
 
     /// Write a frame to the connection
+    ///
+    /// The frame is written along with any queued frame, and flushed.
     pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
-        match frame {
-            Frame::Simple(val) => {
-                self.stream.write_u8(b'+').await?;
-                self.stream.write_all(val.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Error(val) => {
-                self.stream.write_u8(b'+').await?;
-                self.stream.write_all(val.as_bytes()).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Integer(val) => {
-                self.stream.write_u8(b':').await?;
-                self.write_decimal(*val).await?;
-            }
-            Frame::Bulk(val) => {
-                let len = val.len();
+        self.encode(frame);
+        self.flush().await
+    }
 
-                self.stream.write_u8(b'$').await?;
-                self.write_decimal(len as i64).await?;
-                self.stream.write_all(&val).await?;
-                self.stream.write_all(b"\r\n").await?;
-            }
-            Frame::Null => {
-                self.stream.write_all(b"$-1\r\n").await?;
-            }
-            // Encoding an `Array` from within a value cannot be done using a
-            // recursive strategy. In general, async fns do not support
-            // recursion. Mini-redis has not needed to encode nested arrays yet,
-            // so for now it is skipped.
-            Frame::Array(_) => unreachable!(),
+    /// Writes a batch of frames to the connection, then flushes them all at
+    /// once.
+    pub async fn write_frames<'a, I>(&mut self, frames: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = &'a Frame>,
+    {
+        for frame in frames {
+            self.queue_frame(frame).await?;
         }
 
-        self.stream.flush().await?;
+        self.flush().await
+    }
+
+    /// Queues a frame to be written to the connection.
+    ///
+    /// The frame is only written once the flush threshold is reached, or when
+    /// `read_frame` runs out of frames to read, `write_frame` is called or
+    /// the connection is flushed.
+    pub async fn queue_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        self.encode(frame);
+
+        if self.pending.len() >= self.flush_threshold {
+            self.flush().await?;
+        }
 
         Ok(())
     }
 
-    fn parse_frame(&mut self) -> crate::Result<Option<Frame>> {
-        let mut buf = Cursor::new(&self.buffer[..]);
+    /// Writes all queued frames to the stream.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
 
-        match Frame::check(&mut buf) {
-            Ok(_) => {
-                // Get byte length of the frame.
-                let len = buf.position() as usize;
+        let write = async {
+            self.stream.write_all_buf(&mut self.pending).await?;
+            self.stream.flush().await
+        };
 
-                // Reset the internal cursor for the call to `parse`.
-                buf.set_position(0);
+        with_timeout(self.timeouts.write, "write", write).await
+    }
 
-                // Parse the frame.
-                let frame = Frame::parse(&mut buf)?;
+    /// Encodes a frame at the end of the pending bytes.
+    ///
+    /// Encoding synchronously with `Frame::encode` lets arrays be nested to
+    /// any depth, something an `async fn` cannot do as it does not support
+    /// recursion. Frames that only exist in RESP3 are downgraded to their
+    /// RESP2 equivalent unless the connection has switched to RESP3.
+    fn encode(&mut self, frame: &Frame) {
+        frame.encode(&mut self.pending, self.codec.protocol());
+    }
 
-                // Discard the frame from the buffer.
-                self.buffer.advance(len);
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
 
-                Ok(Some(frame))
-            }
-            Err(frame::Error::Incomplete) => Ok(None),
-            Err(e) => Err(e.into()),
-        }
+    /// Returns a mutable reference to the underlying stream.
+    ///
+    /// Reading from or writing to it directly corrupts the framing.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+}
+
+/// Runs `future`, failing with a `TimedOut` error if it does not complete
+/// within `timeout`.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    kind: &str,
+    future: impl Future<Output = io::Result<T>>,
+) -> io::Result<T> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return future.await,
+    };
+
+    match time::timeout(timeout, future).await {
+        Ok(res) => res,
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("{} timeout", kind),
+        )),
+    }
+}
+
+impl<R: AsyncRead + Unpin, W: Unpin> AsyncRead for Join<R, W> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.reader).poll_read(cx, buf)
+    }
+}
+
+impl<R: Unpin, W: AsyncWrite + Unpin> AsyncWrite for Join<R, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.writer).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_shutdown(cx)
     }
 }
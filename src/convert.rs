@@ -0,0 +1,421 @@
+//! Conversions between frames and Rust types.
+//!
+//! `ToFrame` builds the frame sent for a value, and `FromFrame` reads a value
+//! back out of a received frame. Both are implemented for integers, floats,
+//! booleans, strings, `Bytes`, `Option`, `Vec`, tuples and `HashMap`, so
+//! commands and replies can be handled without matching on `Frame` by hand:
+//!
+//! ```
+//! use mini_redis_rs::convert::{FromFrame, ToFrame};
+//! use mini_redis_rs::Frame;
+//!
+//! let frame = ("GET", "hello").to_frame();
+//! let (name, key): (String, String) = FromFrame::from_frame(frame).unwrap();
+//! assert_eq!(("GET", "hello"), (name.as_str(), key.as_str()));
+//!
+//! let value: Option<u64> = FromFrame::from_frame(Frame::Bulk("42".into())).unwrap();
+//! assert_eq!(Some(42), value);
+//! ```
+//!
+//! The conversions accept every shape a value may take in either protocol
+//! version. Numbers are parsed out of strings, as Redis replies with bulk
+//! strings for things like `HGET` on a counter, and maps are read from the
+//! flat arrays RESP2 downgrades them to.
+
+use crate::frame::Frame;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::str;
+
+/// A value that can be written as a frame.
+pub trait ToFrame {
+    /// Returns the frame representing `self`.
+    fn to_frame(&self) -> Frame;
+}
+
+/// A value that can be read out of a frame.
+pub trait FromFrame: Sized {
+    /// Converts `frame` into a value.
+    ///
+    /// An error reply converts into `Error::Reply` for every type but `Frame`
+    /// itself.
+    fn from_frame(frame: Frame) -> Result<Self, Error>;
+}
+
+/// Error returned when a frame cannot be converted into a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The frame is an error reply, holding its message.
+    Reply(String),
+
+    /// The frame's type cannot be converted into the requested value.
+    UnexpectedFrame {
+        expected: &'static str,
+        found: &'static str,
+    },
+
+    /// The frame holds a value that is not valid for the requested type, such
+    /// as a string that is not a number or an integer out of range.
+    InvalidValue {
+        expected: &'static str,
+        value: String,
+    },
+
+    /// An aggregate does not have as many elements as the requested tuple.
+    InvalidLength { expected: usize, found: usize },
+
+    /// A custom error, raised by a serde implementation.
+    Custom(String),
+}
+
+impl ToFrame for Frame {
+    fn to_frame(&self) -> Frame {
+        self.clone()
+    }
+}
+
+impl FromFrame for Frame {
+    fn from_frame(frame: Frame) -> Result<Frame, Error> {
+        Ok(frame)
+    }
+}
+
+impl<T: ToFrame + ?Sized> ToFrame for &T {
+    fn to_frame(&self) -> Frame {
+        (**self).to_frame()
+    }
+}
+
+macro_rules! integer {
+    ($($ty:ty)*) => {$(
+        /// Integers that do not fit in an `i64` are written as big numbers.
+        impl ToFrame for $ty {
+            fn to_frame(&self) -> Frame {
+                i64::try_from(*self)
+                    .map(Frame::Integer)
+                    .unwrap_or_else(|_| Frame::BigNumber(self.to_string()))
+            }
+        }
+
+        impl FromFrame for $ty {
+            fn from_frame(frame: Frame) -> Result<$ty, Error> {
+                let expected = stringify!($ty);
+
+                match frame {
+                    Frame::Integer(val) => {
+                        <$ty>::try_from(val).map_err(|_| Error::invalid(expected, val))
+                    }
+                    frame => parse(expected, frame),
+                }
+            }
+        }
+    )*};
+}
+
+integer!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+macro_rules! float {
+    ($($ty:ty)*) => {$(
+        impl ToFrame for $ty {
+            fn to_frame(&self) -> Frame {
+                Frame::Double(*self as f64)
+            }
+        }
+
+        impl FromFrame for $ty {
+            fn from_frame(frame: Frame) -> Result<$ty, Error> {
+                match frame {
+                    Frame::Double(val) => Ok(val as $ty),
+                    Frame::Integer(val) => Ok(val as $ty),
+                    frame => parse(stringify!($ty), frame),
+                }
+            }
+        }
+    )*};
+}
+
+float!(f32 f64);
+
+impl ToFrame for bool {
+    fn to_frame(&self) -> Frame {
+        Frame::Boolean(*self)
+    }
+}
+
+/// Booleans are read from RESP3 booleans, and from the integers or strings
+/// `0` and `1` used by RESP2.
+impl FromFrame for bool {
+    fn from_frame(frame: Frame) -> Result<bool, Error> {
+        match frame {
+            Frame::Boolean(val) => Ok(val),
+            frame => match i64::from_frame(frame)? {
+                0 => Ok(false),
+                1 => Ok(true),
+                val => Err(Error::invalid("bool", val)),
+            },
+        }
+    }
+}
+
+impl ToFrame for str {
+    fn to_frame(&self) -> Frame {
+        Frame::Bulk(Bytes::copy_from_slice(self.as_bytes()))
+    }
+}
+
+impl ToFrame for String {
+    fn to_frame(&self) -> Frame {
+        self.as_str().to_frame()
+    }
+}
+
+impl FromFrame for String {
+    fn from_frame(frame: Frame) -> Result<String, Error> {
+        let data = Bytes::from_frame(frame)?;
+
+        String::from_utf8(data.to_vec())
+            .map_err(|_| Error::invalid("string", String::from_utf8_lossy(&data)))
+    }
+}
+
+impl ToFrame for Bytes {
+    fn to_frame(&self) -> Frame {
+        Frame::Bulk(self.clone())
+    }
+}
+
+/// Bulk strings are returned without copying their data. Any other frame
+/// holding a single value is converted to its text.
+impl FromFrame for Bytes {
+    fn from_frame(frame: Frame) -> Result<Bytes, Error> {
+        match frame {
+            Frame::Bulk(data) | Frame::Verbatim(_, data) => Ok(data),
+            Frame::Simple(text) | Frame::BigNumber(text) => Ok(Bytes::from(text)),
+            Frame::Integer(val) => Ok(Bytes::from(val.to_string())),
+            Frame::Double(val) => Ok(Bytes::from(val.to_string())),
+            frame => Err(unexpected("string", &frame)),
+        }
+    }
+}
+
+/// `None` is written as a null.
+impl<T: ToFrame> ToFrame for Option<T> {
+    fn to_frame(&self) -> Frame {
+        match self {
+            Some(val) => val.to_frame(),
+            None => Frame::Null,
+        }
+    }
+}
+
+impl<T: FromFrame> FromFrame for Option<T> {
+    fn from_frame(frame: Frame) -> Result<Option<T>, Error> {
+        match frame {
+            Frame::Null | Frame::NullArray => Ok(None),
+            frame => T::from_frame(frame).map(Some),
+        }
+    }
+}
+
+impl<T: ToFrame> ToFrame for [T] {
+    fn to_frame(&self) -> Frame {
+        Frame::Array(self.iter().map(ToFrame::to_frame).collect())
+    }
+}
+
+impl<T: ToFrame> ToFrame for Vec<T> {
+    fn to_frame(&self) -> Frame {
+        self.as_slice().to_frame()
+    }
+}
+
+/// Vectors are read from any aggregate. The entries of a map are read as
+/// arrays holding the key and the value, so a map can be converted into a
+/// `Vec<(K, V)>`. A null converts into an empty vector.
+impl<T: FromFrame> FromFrame for Vec<T> {
+    fn from_frame(frame: Frame) -> Result<Vec<T>, Error> {
+        elements(frame)?.into_iter().map(T::from_frame).collect()
+    }
+}
+
+impl<K, V, S> ToFrame for HashMap<K, V, S>
+where
+    K: ToFrame,
+    V: ToFrame,
+{
+    fn to_frame(&self) -> Frame {
+        Frame::Map(
+            self.iter()
+                .map(|(key, value)| (key.to_frame(), value.to_frame()))
+                .collect(),
+        )
+    }
+}
+
+/// Maps are read from maps, and from arrays alternating keys and values. A
+/// null converts into an empty map.
+impl<K, V, S> FromFrame for HashMap<K, V, S>
+where
+    K: FromFrame + Eq + Hash,
+    V: FromFrame,
+    S: BuildHasher + Default,
+{
+    fn from_frame(frame: Frame) -> Result<HashMap<K, V, S>, Error> {
+        pairs(frame)?
+            .into_iter()
+            .map(|(key, value)| Ok((K::from_frame(key)?, V::from_frame(value)?)))
+            .collect()
+    }
+}
+
+macro_rules! tuple {
+    ($len:expr => $($name:ident $val:ident)+) => {
+        impl<$($name: ToFrame),+> ToFrame for ($($name,)+) {
+            fn to_frame(&self) -> Frame {
+                let ($($val,)+) = self;
+                Frame::Array(vec![$($val.to_frame()),+])
+            }
+        }
+
+        impl<$($name: FromFrame),+> FromFrame for ($($name,)+) {
+            fn from_frame(frame: Frame) -> Result<Self, Error> {
+                let frames = elements(frame)?;
+
+                if frames.len() != $len {
+                    return Err(Error::InvalidLength {
+                        expected: $len,
+                        found: frames.len(),
+                    });
+                }
+
+                let mut frames = frames.into_iter();
+                Ok(($($name::from_frame(frames.next().unwrap())?,)+))
+            }
+        }
+    };
+}
+
+tuple!(1 => A a);
+tuple!(2 => A a B b);
+tuple!(3 => A a B b C c);
+tuple!(4 => A a B b C c D d);
+tuple!(5 => A a B b C c D d E e);
+tuple!(6 => A a B b C c D d E e F f);
+tuple!(7 => A a B b C c D d E e F f G g);
+tuple!(8 => A a B b C c D d E e F f G g H h);
+
+/// Returns the elements of an aggregate frame. Map entries are returned as
+/// two-element arrays.
+pub(crate) fn elements(frame: Frame) -> Result<Vec<Frame>, Error> {
+    match frame {
+        Frame::Array(frames) | Frame::Set(frames) | Frame::Push(frames) => Ok(frames),
+        Frame::Map(pairs) | Frame::Attribute(pairs) => Ok(pairs
+            .into_iter()
+            .map(|(key, value)| Frame::Array(vec![key, value]))
+            .collect()),
+        Frame::Null | Frame::NullArray => Ok(vec![]),
+        frame => Err(unexpected("array", &frame)),
+    }
+}
+
+/// Returns the entries of a map frame, or of an array alternating keys and
+/// values.
+pub(crate) fn pairs(frame: Frame) -> Result<Vec<(Frame, Frame)>, Error> {
+    match frame {
+        Frame::Map(pairs) | Frame::Attribute(pairs) => Ok(pairs),
+        Frame::Array(frames) | Frame::Set(frames) | Frame::Push(frames) => {
+            if frames.len() % 2 != 0 {
+                return Err(Error::InvalidLength {
+                    expected: frames.len() + 1,
+                    found: frames.len(),
+                });
+            }
+
+            let mut frames = frames.into_iter();
+            let mut pairs = Vec::with_capacity(frames.len() / 2);
+
+            while let (Some(key), Some(value)) = (frames.next(), frames.next()) {
+                pairs.push((key, value));
+            }
+
+            Ok(pairs)
+        }
+        Frame::Null | Frame::NullArray => Ok(vec![]),
+        frame => Err(unexpected("map", &frame)),
+    }
+}
+
+/// Parses the text of a single-value frame into a number.
+fn parse<T: str::FromStr>(expected: &'static str, frame: Frame) -> Result<T, Error> {
+    let data = match frame {
+        Frame::Simple(_) | Frame::Bulk(_) | Frame::Verbatim(..) | Frame::BigNumber(_) => {
+            Bytes::from_frame(frame)?
+        }
+        frame => return Err(unexpected(expected, &frame)),
+    };
+
+    str::from_utf8(&data)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| Error::invalid(expected, String::from_utf8_lossy(&data)))
+}
+
+/// Returns the error for a frame that cannot be converted into `expected`.
+pub(crate) fn unexpected(expected: &'static str, frame: &Frame) -> Error {
+    let found = match frame {
+        Frame::Error(msg) => return Error::Reply(msg.clone()),
+        Frame::Simple(_) => "simple string",
+        Frame::Integer(_) => "integer",
+        Frame::Bulk(_) => "bulk string",
+        Frame::Null | Frame::NullArray => "null",
+        Frame::Array(_) => "array",
+        Frame::Map(_) => "map",
+        Frame::Set(_) => "set",
+        Frame::Double(_) => "double",
+        Frame::Boolean(_) => "boolean",
+        Frame::BigNumber(_) => "big number",
+        Frame::Verbatim(..) => "verbatim string",
+        Frame::Push(_) => "push",
+        Frame::Attribute(_) => "attribute",
+    };
+
+    Error::UnexpectedFrame { expected, found }
+}
+
+impl Error {
+    fn invalid(expected: &'static str, value: impl ToString) -> Error {
+        Error::InvalidValue {
+            expected,
+            value: value.to_string(),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Reply(msg) => msg.fmt(fmt),
+            Error::UnexpectedFrame { expected, found } => {
+                write!(
+                    fmt,
+                    "unexpected frame; expected {}, got {}",
+                    expected, found
+                )
+            }
+            Error::InvalidValue { expected, value } => {
+                write!(fmt, "invalid value {:?} for {}", value, expected)
+            }
+            Error::InvalidLength { expected, found } => write!(
+                fmt,
+                "invalid length; expected {} elements, got {}",
+                expected, found
+            ),
+            Error::Custom(msg) => msg.fmt(fmt),
+        }
+    }
+}
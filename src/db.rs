@@ -0,0 +1,1439 @@
+use crate::glob::glob_match;
+use crate::shard_db;
+use crate::value::{Value, WrongType};
+use crate::{Frame, ToFrame};
+use bytes::Bytes;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, oneshot, Notify};
+use tokio::time::{self, Duration, Instant};
+
+/// Number of shards the keys are spread over. Commands on keys in different
+/// shards do not contend on the same lock.
+const SHARDS: usize = 8;
+
+/// The keyspace, shared by every connection.
+///
+/// Keys are spread over several independently locked shards. Locks are only
+/// held for the duration of a single operation and never across an `.await`.
+///
+/// Keys may be given an expiration. An expired key is removed the next time
+/// it is accessed, and each shard has a background task removing the keys
+/// that are not accessed anymore once they expire. The tasks are spawned by
+/// `Db::new`, which must therefore be called from within a Tokio runtime, and
+/// stop when the `Db` is dropped.
+///
+/// The `Db` also holds the pub/sub channels, which are unrelated to the keys.
+pub struct Db {
+    shards: Vec<Arc<Shard>>,
+
+    /// The pub/sub key-space. Redis uses a **separate** key space for key-value
+    /// and pub/sub. `mini-redis` handles this by using a separate `HashMap`.
+    pub_sub: Mutex<PubSub>,
+}
+
+/// Channels and patterns subscribed to.
+///
+/// Every subscription holds a `broadcast::Receiver`, so the number of
+/// subscribers is the number of receivers. Senders nobody receives from
+/// anymore are removed when publishing.
+#[derive(Debug, Default)]
+struct PubSub {
+    channels: HashMap<String, broadcast::Sender<Bytes>>,
+
+    /// Senders of the messages published on channels matching each pattern,
+    /// along with the channel they were published on.
+    patterns: HashMap<String, broadcast::Sender<(String, Bytes)>>,
+}
+
+/// A subset of the keys, along with the task purging them.
+#[derive(Debug)]
+struct Shard {
+    state: Mutex<State>,
+
+    /// Notifies the background task purging expired keys. The task waits for
+    /// the next key to expire, so it must be woken up when a key expiring
+    /// sooner is added, or when the `Db` is dropped.
+    background_task: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    entries: HashMap<String, Entry>,
+
+    /// Keys with an expiration, ordered by when they expire. Including the
+    /// key makes entries unique even when several keys expire at the same
+    /// instant.
+    expirations: BTreeSet<(Instant, String)>,
+
+    /// Clients blocked on each key, in the order they blocked.
+    blocked: HashMap<String, VecDeque<Blocked>>,
+
+    /// `true` once the `Db` is dropped, which stops the background task.
+    shutdown: bool,
+}
+
+/// A client blocked on a key by `Db::pop_or_block` or `Db::lmove_or_block`,
+/// queued on every key it waits for.
+#[derive(Debug)]
+struct Blocked {
+    /// End of the list the client pops from.
+    end: End,
+
+    /// Where the value popped is moved to, for a `BLMOVE` client.
+    destination: Option<Destination>,
+
+    slot: Slot,
+}
+
+/// The list a `BLMOVE` client pushes the value it pops to.
+#[derive(Debug, Clone)]
+struct Destination {
+    key: String,
+    end: End,
+
+    /// Index of the shard holding `key`, which must be locked to serve the
+    /// client.
+    shard: usize,
+}
+
+/// What a blocked client is served: the key a value was popped from along
+/// with the value, or the error preventing a `BLMOVE` client from moving it.
+type Served = Result<(String, Bytes), WrongType>;
+
+/// Where to send what a blocked client is served. It is shared by the queues
+/// of every key the client waits for, and taken by whichever serves it first,
+/// or by the `Waiter` when it gives up.
+type Slot = Arc<Mutex<Option<oneshot::Sender<Served>>>>;
+
+/// Shards locked together, along with their index.
+type Locked<'a> = Vec<(usize, MutexGuard<'a, State>)>;
+
+/// Result of `Db::pop_or_block` and `Db::lmove_or_block`.
+#[derive(Debug)]
+pub enum BlockingPop {
+    /// A value was popped, or moved, right away from the given key.
+    Popped(String, Bytes),
+
+    /// All the lists were empty, the client is now waiting for a push.
+    Blocked(Waiter),
+}
+
+/// A client blocked until a value is pushed to one of the lists it waits
+/// for.
+///
+/// Clients blocked on the same key are served in the order they blocked: a
+/// value pushed is popped on behalf of the first one and handed to it, so
+/// no other client can take it in the meantime.
+///
+/// Dropping the `Waiter`, e.g. when the client disconnects or its timeout
+/// expires, removes it from the queues. If a value was popped for it but not
+/// received yet, the value is pushed back where it came from, unless it was
+/// already moved to a `BLMOVE` destination.
+#[derive(Debug)]
+pub struct Waiter {
+    /// Keys waited for.
+    keys: Vec<String>,
+
+    /// Every shard of the `Db`, to find the keys in.
+    shards: Vec<Arc<Shard>>,
+
+    /// End of the lists values are popped from, where a value that is not
+    /// received is pushed back to.
+    end: End,
+
+    /// `true` if the value is moved to a destination when the client is
+    /// served, like `BLMOVE` does.
+    moves: bool,
+
+    slot: Slot,
+    rx: oneshot::Receiver<Served>,
+}
+
+/// Entry in the key-value store
+#[derive(Debug)]
+struct Entry {
+    /// Stored value
+    value: Value,
+
+    /// Instant at which the entry expires and should be removed from the
+    /// database.
+    expires_at: Option<Instant>,
+}
+
+/// What happens to the expiration of a key being written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    /// The key keeps its current expiration, if any.
+    Keep,
+
+    /// The key does not expire anymore.
+    Persist,
+
+    /// The key expires at the given instant.
+    At(Instant),
+}
+
+/// End of a list values are pushed to or popped from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    /// The head of the list, like `LPUSH` and `LPOP`.
+    Left,
+
+    /// The tail of the list, like `RPUSH` and `RPOP`.
+    Right,
+}
+
+/// Condition for `Db::set` to write the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Always,
+
+    /// Only if the key does not exist, like `SET NX`.
+    IfAbsent,
+
+    /// Only if the key exists, like `SET XX`.
+    IfPresent,
+}
+
+impl Db {
+    pub fn new() -> Db {
+        let shards = (0..SHARDS)
+            .map(|_| {
+                let shard = Arc::new(Shard {
+                    state: Mutex::new(State::default()),
+                    background_task: Notify::new(),
+                });
+
+                tokio::spawn(purge_expired_tasks(shard.clone()));
+                shard
+            })
+            .collect();
+
+        Db {
+            shards,
+            pub_sub: Mutex::new(PubSub::default()),
+        }
+    }
+
+    /// Returns the shard holding `key`.
+    fn shard(&self, key: &str) -> &Shard {
+        &self.shards[self.shard_index(key)]
+    }
+
+    /// Returns the index in `shards` of the shard holding `key`.
+    fn shard_index(&self, key: &str) -> usize {
+        shard_index(&self.shards, key)
+    }
+
+    /// Returns the string value of `key`, or `None` if there is no such key.
+    pub fn get(&self, key: &str) -> Result<Option<Bytes>, WrongType> {
+        let mut state = self.shard(key).state.lock().unwrap();
+
+        match state.entry(key) {
+            Some(entry) => Ok(Some(entry.value.as_string()?.clone())),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the string value of `key` and changes its expiration, like
+    /// `GETEX`.
+    pub fn get_ex(&self, key: &str, expiry: Expiry) -> Result<Option<Bytes>, WrongType> {
+        let shard = self.shard(key);
+        let mut state = shard.state.lock().unwrap();
+
+        let data = match state.entry(key) {
+            Some(entry) => entry.value.as_string()?.clone(),
+            None => return Ok(None),
+        };
+        let notify = state.set_expiry(key, expiry);
+
+        drop(state);
+        shard.notify_if(notify);
+
+        Ok(Some(data))
+    }
+
+    /// Returns the kind of value held by `key`, as reported by `TYPE`, or
+    /// `None` if there is no such key.
+    pub fn value_type(&self, key: &str) -> Option<&'static str> {
+        let mut state = self.shard(key).state.lock().unwrap();
+        state.entry(key).map(|entry| entry.value.type_name())
+    }
+
+    /// Sets `key` to the string `value` if `condition` holds, giving it the
+    /// expiration `expiry`. Whatever the key held before is replaced.
+    ///
+    /// Returns whether the key was set, along with its previous value if it
+    /// was a string. With `get`, the previous value must be a string: if it
+    /// is not, the key is left untouched and `WrongType` is returned, like
+    /// `SET` with the `GET` option.
+    pub fn set(
+        &self,
+        key: String,
+        value: Bytes,
+        expiry: Expiry,
+        condition: Condition,
+        get: bool,
+    ) -> Result<(bool, Option<Bytes>), WrongType> {
+        let shard = self.shard(&key);
+        let mut state = shard.state.lock().unwrap();
+
+        let (exists, previous) = match state.entry(&key) {
+            Some(entry) => match entry.value.as_string() {
+                Ok(data) => (true, Some(data.clone())),
+                Err(err) if get => return Err(err),
+                Err(_) => (true, None),
+            },
+            None => (false, None),
+        };
+
+        let set = match condition {
+            Condition::Always => true,
+            Condition::IfAbsent => !exists,
+            Condition::IfPresent => exists,
+        };
+
+        if !set {
+            return Ok((false, previous));
+        }
+
+        let expires_at = match expiry {
+            Expiry::Keep => state.entries.get(&key).and_then(|entry| entry.expires_at),
+            _ => {
+                state.remove(&key);
+                None
+            }
+        };
+
+        state.entries.insert(
+            key.clone(),
+            Entry {
+                value: Value::String(value),
+                expires_at,
+            },
+        );
+
+        let notify = state.set_expiry(&key, expiry);
+
+        drop(state);
+        shard.notify_if(notify);
+
+        Ok((true, previous))
+    }
+
+    /// Sets `key` to `value`, whatever kind of value it is, replacing what the
+    /// key held before along with its expiration.
+    pub fn set_value(&self, key: String, value: Value) {
+        let mut states = lock_for_serving(&self.shards, &[&key]);
+        let state = locked_state(&mut states, self.shard_index(&key));
+
+        state.remove(&key);
+        state.entries.insert(
+            key.clone(),
+            Entry {
+                value,
+                expires_at: None,
+            },
+        );
+        serve_blocked(&self.shards, states, &[&key]);
+    }
+
+    /// Makes `key` expire at `when`. A key expiring in the past is removed
+    /// right away.
+    ///
+    /// Returns `false` if there is no such key.
+    pub fn expire(&self, key: &str, when: Instant) -> bool {
+        let shard = self.shard(key);
+        let mut state = shard.state.lock().unwrap();
+
+        if state.entry(key).is_none() {
+            return false;
+        }
+
+        if when <= Instant::now() {
+            state.remove(key);
+            return true;
+        }
+
+        let notify = state.set_expiry(key, Expiry::At(when));
+
+        drop(state);
+        shard.notify_if(notify);
+
+        true
+    }
+
+    /// Removes the expiration of `key`.
+    ///
+    /// Returns `false` if there is no such key, or it had no expiration.
+    pub fn persist(&self, key: &str) -> bool {
+        let mut state = self.shard(key).state.lock().unwrap();
+
+        match state.entry(key) {
+            Some(entry) if entry.expires_at.is_some() => {
+                state.set_expiry(key, Expiry::Persist);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns how long `key` has left to live.
+    ///
+    /// `None` means there is no such key, `Some(None)` that the key does not
+    /// expire.
+    pub fn ttl(&self, key: &str) -> Option<Option<Duration>> {
+        let mut state = self.shard(key).state.lock().unwrap();
+        let entry = state.entry(key)?;
+
+        Some(
+            entry
+                .expires_at
+                .map(|when| when.saturating_duration_since(Instant::now())),
+        )
+    }
+
+    /// Pushes `values` one after the other to the `end` of the list held by
+    /// `key`, creating the list if there is no such key, like `LPUSH` and
+    /// `RPUSH`.
+    ///
+    /// Returns the length of the list.
+    pub fn push(&self, key: String, end: End, values: Vec<Bytes>) -> Result<usize, WrongType> {
+        let mut states = lock_for_serving(&self.shards, &[&key]);
+        let state = locked_state(&mut states, self.shard_index(&key));
+        let list = state.list_or_default(&key)?;
+
+        for value in values {
+            match end {
+                End::Left => list.push_front(value),
+                End::Right => list.push_back(value),
+            }
+        }
+
+        let len = list.len();
+        state.remove_if_empty(&key);
+        serve_blocked(&self.shards, states, &[&key]);
+
+        Ok(len)
+    }
+
+    /// Pops a value from the `end` of the first non-empty list among `keys`,
+    /// like `BLPOP` and `BRPOP`. If they are all empty, the client is blocked
+    /// on every key, and the returned `Waiter` receives the first value
+    /// pushed to one of them.
+    ///
+    /// Keys holding another kind of value are an error, unless a list before
+    /// them has a value.
+    pub fn pop_or_block(&self, keys: &[String], end: End) -> Result<BlockingPop, WrongType> {
+        // Every shard involved is locked for the whole operation, so that no
+        // value can be pushed between checking the lists and blocking on them.
+        let indexes = keys.iter().map(|key| self.shard_index(key)).collect();
+        let mut states = lock_shards(&self.shards, &indexes);
+
+        for key in keys {
+            let state = locked_state(&mut states, self.shard_index(key));
+
+            if let Some(list) = state.list(key)? {
+                let value = match end {
+                    End::Left => list.pop_front(),
+                    End::Right => list.pop_back(),
+                };
+                state.remove_if_empty(key);
+
+                if let Some(value) = value {
+                    return Ok(BlockingPop::Popped(key.clone(), value));
+                }
+            }
+        }
+
+        Ok(BlockingPop::Blocked(self.block(
+            &mut states,
+            keys,
+            end,
+            None,
+        )))
+    }
+
+    /// Moves a value from `source` to `destination` like `lmove`, or if there
+    /// is no `source` key, blocks until a value is pushed to it, like
+    /// `BLMOVE`. The returned `Waiter` is served once the value has been
+    /// moved.
+    ///
+    /// Fails right away if `destination` holds another kind of value. If it
+    /// does by the time the client is served, nothing is moved and the
+    /// `Waiter` receives the error instead.
+    pub fn lmove_or_block(
+        &self,
+        source: &str,
+        destination: &str,
+        from: End,
+        to: End,
+    ) -> Result<BlockingPop, WrongType> {
+        let mut states = lock_for_serving(&self.shards, &[source, destination]);
+        let destination_index = self.shard_index(destination);
+
+        locked_state(&mut states, destination_index).list(destination)?;
+
+        if let Some(value) = move_value(&self.shards, &mut states, source, destination, from, to)? {
+            serve_blocked(&self.shards, states, &[destination]);
+            return Ok(BlockingPop::Popped(source.to_string(), value));
+        }
+
+        let destination = Destination {
+            key: destination.to_string(),
+            end: to,
+            shard: destination_index,
+        };
+        let source = [source.to_string()];
+
+        Ok(BlockingPop::Blocked(self.block(
+            &mut states,
+            &source,
+            from,
+            Some(destination),
+        )))
+    }
+
+    /// Blocks a client on every one of `keys`, whose shards are among the
+    /// locked `states`.
+    fn block(
+        &self,
+        states: &mut Locked<'_>,
+        keys: &[String],
+        end: End,
+        destination: Option<Destination>,
+    ) -> Waiter {
+        let (tx, rx) = oneshot::channel();
+        let slot = Arc::new(Mutex::new(Some(tx)));
+        let moves = destination.is_some();
+
+        for key in keys {
+            let state = locked_state(states, self.shard_index(key));
+
+            state
+                .blocked
+                .entry(key.clone())
+                .or_default()
+                .push_back(Blocked {
+                    end,
+                    destination: destination.clone(),
+                    slot: slot.clone(),
+                });
+        }
+
+        Waiter {
+            keys: keys.to_vec(),
+            shards: self.shards.clone(),
+            end,
+            moves,
+            slot,
+            rx,
+        }
+    }
+
+    /// Removes up to `count` values from the `end` of the list held by `key`,
+    /// like `LPOP` and `RPOP`. The values are returned in the order they were
+    /// popped.
+    ///
+    /// Returns `None` if there is no such key.
+    pub fn pop(&self, key: &str, end: End, count: usize) -> Result<Option<Vec<Bytes>>, WrongType> {
+        let mut state = self.shard(key).state.lock().unwrap();
+
+        let list = match state.list(key)? {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+
+        let count = count.min(list.len());
+        let values = match end {
+            End::Left => list.drain(..count).collect(),
+            End::Right => list.drain(list.len() - count..).rev().collect(),
+        };
+
+        state.remove_if_empty(key);
+
+        Ok(Some(values))
+    }
+
+    /// Returns the values of the list held by `key` from index `start` to
+    /// `stop` included, like `LRANGE`. Negative indexes count from the end of
+    /// the list.
+    pub fn lrange(&self, key: &str, start: i64, stop: i64) -> Result<Vec<Bytes>, WrongType> {
+        let mut state = self.shard(key).state.lock().unwrap();
+
+        match state.list(key)? {
+            Some(list) => Ok(list
+                .range(list_range(list.len(), start, stop))
+                .cloned()
+                .collect()),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Returns the length of the list held by `key`, `0` if there is no such
+    /// key.
+    pub fn llen(&self, key: &str) -> Result<usize, WrongType> {
+        let mut state = self.shard(key).state.lock().unwrap();
+        Ok(state.list(key)?.map_or(0, |list| list.len()))
+    }
+
+    /// Returns the value at `index` in the list held by `key`, where negative
+    /// indexes count from the end of the list.
+    pub fn lindex(&self, key: &str, index: i64) -> Result<Option<Bytes>, WrongType> {
+        let mut state = self.shard(key).state.lock().unwrap();
+
+        let value = state
+            .list(key)?
+            .and_then(|list| list.get(list_index(list.len(), index)?).cloned());
+
+        Ok(value)
+    }
+
+    /// Replaces the value at `index` in the list held by `key`, where negative
+    /// indexes count from the end of the list.
+    ///
+    /// `None` means there is no such key, `Some(false)` that the index is out
+    /// of range.
+    pub fn lset(&self, key: &str, index: i64, value: Bytes) -> Result<Option<bool>, WrongType> {
+        let mut state = self.shard(key).state.lock().unwrap();
+
+        let list = match state.list(key)? {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+
+        match list_index(list.len(), index) {
+            Some(index) => {
+                list[index] = value;
+                Ok(Some(true))
+            }
+            None => Ok(Some(false)),
+        }
+    }
+
+    /// Removes the values equal to `value` from the list held by `key`, like
+    /// `LREM`. With a positive `count`, at most `count` values are removed
+    /// starting from the head of the list, with a negative one starting from
+    /// the tail. A `count` of `0` removes them all.
+    ///
+    /// Returns the number of values removed.
+    pub fn lrem(&self, key: &str, count: i64, value: &[u8]) -> Result<usize, WrongType> {
+        let mut state = self.shard(key).state.lock().unwrap();
+
+        let list = match state.list(key)? {
+            Some(list) => list,
+            None => return Ok(0),
+        };
+
+        let limit = match count {
+            0 => usize::MAX,
+            count => usize::try_from(count.unsigned_abs()).unwrap_or(usize::MAX),
+        };
+
+        let mut removed = 0;
+
+        if count >= 0 {
+            list.retain(|item| {
+                let remove = removed < limit && item == value;
+                removed += usize::from(remove);
+                !remove
+            });
+        } else {
+            let mut index = list.len();
+
+            while index > 0 && removed < limit {
+                index -= 1;
+
+                if list[index] == value {
+                    list.remove(index);
+                    removed += 1;
+                }
+            }
+        }
+
+        state.remove_if_empty(key);
+
+        Ok(removed)
+    }
+
+    /// Trims the list held by `key` to the values from index `start` to `stop`
+    /// included, like `LTRIM`. Negative indexes count from the end of the
+    /// list.
+    pub fn ltrim(&self, key: &str, start: i64, stop: i64) -> Result<(), WrongType> {
+        let mut state = self.shard(key).state.lock().unwrap();
+
+        if let Some(list) = state.list(key)? {
+            let range = list_range(list.len(), start, stop);
+
+            list.truncate(range.end);
+            list.drain(..range.start);
+        }
+
+        state.remove_if_empty(key);
+
+        Ok(())
+    }
+
+    /// Pops a value from the `from` end of the list held by `source` and
+    /// pushes it to the `to` end of the list held by `destination`, creating
+    /// it if needed, like `LMOVE`. The source and destination may be the same
+    /// list.
+    ///
+    /// Returns the value moved, or `None` if there is no `source` key. Nothing
+    /// is moved if `destination` holds another kind of value.
+    pub fn lmove(
+        &self,
+        source: &str,
+        destination: &str,
+        from: End,
+        to: End,
+    ) -> Result<Option<Bytes>, WrongType> {
+        let mut states = lock_for_serving(&self.shards, &[source, destination]);
+        let value = move_value(&self.shards, &mut states, source, destination, from, to)?;
+
+        serve_blocked(&self.shards, states, &[destination]);
+        Ok(value)
+    }
+
+    /// Returns a `Receiver` for the requested channel.
+    ///
+    /// The returned `Receiver` is used to receive values broadcast by `PUBLISH`
+    /// commands.
+    pub fn subscribe(&self, key: String) -> broadcast::Receiver<Bytes> {
+        let mut pub_sub = self.pub_sub.lock().unwrap();
+        receiver(&mut pub_sub.channels, key)
+    }
+
+    /// Returns a `Receiver` for the messages published on the channels
+    /// matching `pattern`, which are received along with their channel.
+    pub fn psubscribe(&self, pattern: String) -> broadcast::Receiver<(String, Bytes)> {
+        let mut pub_sub = self.pub_sub.lock().unwrap();
+        receiver(&mut pub_sub.patterns, pattern)
+    }
+
+    /// Publish a message to the channel. Returns the number of subscribers
+    /// the message was sent to, counting both the subscribers to the channel
+    /// and those to the patterns matching it.
+    pub fn publish(&self, key: &str, value: Bytes) -> usize {
+        let mut pub_sub = self.pub_sub.lock().unwrap();
+
+        // On a successful message send on the broadcast channel, the number
+        // of subscribers is returned. An error indicates there are no
+        // receivers, in which case, `0` should be returned.
+        let mut sent = pub_sub
+            .channels
+            .get(key)
+            .map(|tx| tx.send(value.clone()).unwrap_or(0))
+            .unwrap_or(0);
+
+        for (pattern, tx) in &pub_sub.patterns {
+            if glob_match(pattern.as_bytes(), key.as_bytes()) {
+                sent += tx.send((key.to_string(), value.clone())).unwrap_or(0);
+            }
+        }
+
+        pub_sub.channels.retain(|_, tx| tx.receiver_count() > 0);
+        pub_sub.patterns.retain(|_, tx| tx.receiver_count() > 0);
+
+        sent
+    }
+
+    /// Returns the channels with at least one subscriber, only keeping those
+    /// matching `pattern` if given.
+    ///
+    /// Pattern subscriptions are not taken into account.
+    pub fn channels(&self, pattern: Option<&str>) -> Vec<String> {
+        let pub_sub = self.pub_sub.lock().unwrap();
+
+        pub_sub
+            .channels
+            .iter()
+            .filter(|(channel, tx)| {
+                tx.receiver_count() > 0
+                    && pattern
+                        .is_none_or(|pattern| glob_match(pattern.as_bytes(), channel.as_bytes()))
+            })
+            .map(|(channel, _)| channel.clone())
+            .collect()
+    }
+
+    /// Returns the number of subscribers to `channel`, not counting those to
+    /// the patterns matching it.
+    pub fn num_subscribers(&self, channel: &str) -> usize {
+        let pub_sub = self.pub_sub.lock().unwrap();
+
+        pub_sub
+            .channels
+            .get(channel)
+            .map_or(0, |tx| tx.receiver_count())
+    }
+
+    /// Returns the number of distinct patterns subscribed to.
+    pub fn num_patterns(&self) -> usize {
+        let pub_sub = self.pub_sub.lock().unwrap();
+
+        pub_sub
+            .patterns
+            .values()
+            .filter(|tx| tx.receiver_count() > 0)
+            .count()
+    }
+
+    /// Returns the commands recreating every key, one batch per shard.
+    ///
+    /// Strings with an expiration are set with `PXAT`, and other values are
+    /// followed by `PEXPIREAT`, so that keys expire at the same time once
+    /// recreated. Hashes, sets and sorted sets are left out until there are
+    /// commands to recreate them.
+    ///
+    /// A shard is only locked while its batch is being built, so writers are
+    /// not held up for the whole dump.
+    pub fn dump(&self) -> impl Iterator<Item = Vec<Frame>> + '_ {
+        self.shards.iter().map(|shard| {
+            let now = Instant::now();
+            let unix_now = unix_time();
+
+            let state = shard.state.lock().unwrap();
+            let mut commands = vec![];
+
+            for (key, entry) in &state.entries {
+                let expires_at = match entry.expires_at {
+                    Some(when) if when <= now => continue,
+                    Some(when) => {
+                        let at = unix_now + (when - now);
+                        Some(at.as_millis().to_string())
+                    }
+                    None => None,
+                };
+
+                match (&entry.value, expires_at) {
+                    (Value::String(data), Some(at)) => {
+                        commands.push(("SET", key, data, "PXAT", at).to_frame());
+                    }
+                    (value, expires_at) => {
+                        match restore(key, value) {
+                            Some(command) => commands.push(command),
+                            None => continue,
+                        }
+
+                        if let Some(at) = expires_at {
+                            commands.push(("PEXPIREAT", key, at).to_frame());
+                        }
+                    }
+                }
+            }
+
+            commands
+        })
+    }
+}
+
+impl Default for Db {
+    fn default() -> Db {
+        Db::new()
+    }
+}
+
+impl Drop for Db {
+    fn drop(&mut self) {
+        // Signal the background tasks to shut down.
+        for shard in &self.shards {
+            shard.state.lock().unwrap().shutdown = true;
+            shard.background_task.notify_one();
+        }
+    }
+}
+
+/// Returns a `Receiver` for the sender of `key` in `senders`, creating it if
+/// needed.
+fn receiver<T: Clone>(
+    senders: &mut HashMap<String, broadcast::Sender<T>>,
+    key: String,
+) -> broadcast::Receiver<T> {
+    use std::collections::hash_map::Entry;
+
+    // If there is no entry for the requested channel, then create a new
+    // broadcast channel and associate it with the key. If one already exists,
+    // return an associated receiver.
+    match senders.entry(key) {
+        Entry::Occupied(e) => e.get().subscribe(),
+        Entry::Vacant(e) => {
+            // No broadcast channel exists yet, so create one.
+            //
+            // The channel is created with a capacity of `1024` messages. A
+            // message is stored in the channel until **all** subscribers have
+            // seen it. This means that a slow subscriber could result in
+            // messages being held indefinitely.
+            //
+            // When the channel's capacity fills up, publishing will result in
+            // old messages being dropped. This prevents slow consumers from
+            // blocking the entire system.
+            let (tx, rx) = broadcast::channel(1024);
+            e.insert(tx);
+            rx
+        }
+    }
+}
+
+/// Returns the command recreating `key`, which holds `value`, without its
+/// expiration.
+///
+/// Returns `None` for the kinds of values no command can create yet, which
+/// could not be loaded back.
+fn restore(key: &str, value: &Value) -> Option<Frame> {
+    let (name, args): (_, Vec<Frame>) = match value {
+        Value::String(data) => ("SET", vec![data.to_frame()]),
+        Value::List(list) => ("RPUSH", list.iter().map(ToFrame::to_frame).collect()),
+        Value::Hash(_) | Value::Set(_) | Value::SortedSet(_) => return None,
+    };
+
+    let mut command = vec![name.to_frame(), key.to_frame()];
+    command.extend(args);
+    Some(Frame::Array(command))
+}
+
+/// Returns the index in `shards` of the shard holding `key`.
+fn shard_index(shards: &[Arc<Shard>], key: &str) -> usize {
+    shard_db::hash(key.to_string()) % shards.len()
+}
+
+/// Locks the shards at `indexes`. They are locked in the order of their
+/// index, so that two operations locking several shards cannot deadlock.
+fn lock_shards<'a>(shards: &'a [Arc<Shard>], indexes: &BTreeSet<usize>) -> Locked<'a> {
+    indexes
+        .iter()
+        .map(|&index| (index, shards[index].state.lock().unwrap()))
+        .collect()
+}
+
+/// Locks the shards holding `keys`, along with the shards holding the
+/// destinations of the `BLMOVE` clients blocked on them, so that the clients
+/// can be served once the keys are pushed to.
+fn lock_for_serving<'a>(shards: &'a [Arc<Shard>], keys: &[&str]) -> Locked<'a> {
+    let mut indexes: BTreeSet<usize> = keys.iter().map(|key| shard_index(shards, key)).collect();
+
+    loop {
+        let mut states = lock_shards(shards, &indexes);
+        let mut needed = indexes.clone();
+
+        for key in keys {
+            let state = locked_state(&mut states, shard_index(shards, key));
+            let destinations = state
+                .blocked
+                .get(*key)
+                .into_iter()
+                .flatten()
+                .filter_map(|blocked| blocked.destination.as_ref());
+
+            needed.extend(destinations.map(|destination| destination.shard));
+        }
+
+        if needed == indexes {
+            return states;
+        }
+
+        // Shards can only be locked in order, so start over with all of them.
+        drop(states);
+        indexes = needed;
+    }
+}
+
+/// Serves the clients blocked on `keys`, which may just have been pushed to,
+/// then unlocks `states`.
+///
+/// Serving a `BLMOVE` client pushes to its destination, whose own blocked
+/// clients are served in turn. A client whose destination is in a shard that
+/// is not locked, and the clients queued behind it, are served afterwards,
+/// locking the shards they need.
+fn serve_blocked<'a>(shards: &'a [Arc<Shard>], mut states: Locked<'a>, keys: &[&str]) {
+    let mut ready: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+
+    loop {
+        let mut deferred = vec![];
+
+        while let Some(key) = ready.pop() {
+            serve_key(shards, &mut states, &key, &mut ready, &mut deferred);
+        }
+
+        drop(states);
+
+        if deferred.is_empty() {
+            return;
+        }
+
+        let keys: Vec<&str> = deferred.iter().map(String::as_str).collect();
+        states = lock_for_serving(shards, &keys);
+        ready = deferred;
+    }
+}
+
+/// Hands the values of the list held by `key` to the clients blocked on it,
+/// in the order they blocked, until either runs out.
+///
+/// The destinations values are moved to are added to `ready`. If the next
+/// client's destination is in a shard that is not locked, `key` is added to
+/// `deferred` instead of serving it.
+fn serve_key(
+    shards: &[Arc<Shard>],
+    states: &mut Locked<'_>,
+    key: &str,
+    ready: &mut Vec<String>,
+    deferred: &mut Vec<String>,
+) {
+    let index = shard_index(shards, key);
+
+    loop {
+        let state = locked_state(states, index);
+        let has_value = matches!(state.list(key), Ok(Some(list)) if !list.is_empty());
+
+        let destination = match state.blocked.get(key).and_then(VecDeque::front) {
+            Some(blocked) if has_value => blocked.destination.as_ref().map(|d| d.shard),
+            _ => break,
+        };
+
+        if let Some(shard) = destination {
+            if !states.iter().any(|(locked, _)| *locked == shard) {
+                deferred.push(key.to_string());
+                break;
+            }
+        }
+
+        let blocked = match locked_state(states, index)
+            .blocked
+            .get_mut(key)
+            .and_then(VecDeque::pop_front)
+        {
+            Some(blocked) => blocked,
+            None => break,
+        };
+
+        let mut slot = blocked.slot.lock().unwrap();
+
+        // The client may have been served through another key already, or
+        // have given up.
+        if slot.is_none() {
+            continue;
+        }
+
+        // Like Redis, nothing is popped if the destination of a `BLMOVE`
+        // client holds another kind of value by now, and the client is served
+        // the error.
+        if let Some(destination) = &blocked.destination {
+            if let Err(err) = locked_state(states, destination.shard).list(&destination.key) {
+                let _ = send(&mut slot, Err(err));
+                continue;
+            }
+        }
+
+        // The list was checked not to be empty.
+        let value = match locked_state(states, index).list(key) {
+            Ok(Some(list)) => match blocked.end {
+                End::Left => list.pop_front(),
+                End::Right => list.pop_back(),
+            },
+            _ => None,
+        };
+        let value = match value {
+            Some(value) => value,
+            None => break,
+        };
+
+        match blocked.destination {
+            Some(destination) => {
+                let state = locked_state(states, destination.shard);
+
+                if let Ok(list) = state.list_or_default(&destination.key) {
+                    match destination.end {
+                        End::Left => list.push_front(value.clone()),
+                        End::Right => list.push_back(value.clone()),
+                    }
+                }
+
+                // The value is moved whether the client is still there to be
+                // told or not.
+                let _ = send(&mut slot, Ok((key.to_string(), value)));
+                ready.push(destination.key);
+            }
+            None => {
+                // The slot is still locked while sending, so a `Waiter` giving
+                // up at the same time finds the value in its receiver.
+                if let Err(Ok((_, value))) = send(&mut slot, Ok((key.to_string(), value))) {
+                    if let Ok(Some(list)) = locked_state(states, index).list(key) {
+                        match blocked.end {
+                            End::Left => list.push_front(value),
+                            End::Right => list.push_back(value),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let state = locked_state(states, index);
+
+    if state.blocked.get(key).is_some_and(VecDeque::is_empty) {
+        state.blocked.remove(key);
+    }
+
+    state.remove_if_empty(key);
+}
+
+/// Serves a blocked client through its `slot`, returning what it is served
+/// back if it is gone.
+fn send(slot: &mut Option<oneshot::Sender<Served>>, served: Served) -> Result<(), Served> {
+    match slot.take() {
+        Some(tx) => tx.send(served),
+        None => Err(served),
+    }
+}
+
+/// Moves a value from `source` to `destination` like `Db::lmove`, with the
+/// shards holding both keys among the locked `states`.
+fn move_value(
+    shards: &[Arc<Shard>],
+    states: &mut Locked<'_>,
+    source: &str,
+    destination: &str,
+    from: End,
+    to: End,
+) -> Result<Option<Bytes>, WrongType> {
+    let source_index = shard_index(shards, source);
+    let destination_index = shard_index(shards, destination);
+
+    if locked_state(states, source_index).list(source)?.is_none() {
+        return Ok(None);
+    }
+
+    locked_state(states, destination_index).list(destination)?;
+
+    let value = match locked_state(states, source_index).list(source)? {
+        Some(list) => match from {
+            End::Left => list.pop_front(),
+            End::Right => list.pop_back(),
+        },
+        None => None,
+    };
+
+    if let Some(value) = value.clone() {
+        let list = locked_state(states, destination_index).list_or_default(destination)?;
+
+        match to {
+            End::Left => list.push_front(value),
+            End::Right => list.push_back(value),
+        }
+    }
+
+    // The source is only removed once the value has been pushed, so that
+    // rotating a list of one value keeps the key, and its expiration.
+    locked_state(states, source_index).remove_if_empty(source);
+
+    Ok(value)
+}
+
+/// Returns the state of the shard at `index` among the locked `states`.
+fn locked_state<'a>(
+    states: &'a mut [(usize, MutexGuard<'_, State>)],
+    index: usize,
+) -> &'a mut State {
+    let position = states
+        .iter()
+        .position(|(locked, _)| *locked == index)
+        .expect("shard not locked");
+
+    &mut states[position].1
+}
+
+/// Returns the range of indexes from `start` to `stop` included in a list of
+/// `len` values, where negative indexes count from the end of the list.
+///
+/// Like Redis, indexes past either end are clamped, and the range is empty if
+/// `start` comes after `stop`.
+fn list_range(len: usize, start: i64, stop: i64) -> Range<usize> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (start + len).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        stop + len
+    } else {
+        stop.min(len - 1)
+    };
+
+    if start > stop || start >= len {
+        return 0..0;
+    }
+
+    start as usize..stop as usize + 1
+}
+
+/// Returns the position of `index` in a list of `len` values, where negative
+/// indexes count from the end of the list, or `None` if it is out of range.
+fn list_index(len: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 { index + len as i64 } else { index };
+
+    usize::try_from(index).ok().filter(|&index| index < len)
+}
+
+/// Returns the time elapsed since the Unix epoch.
+pub(crate) fn unix_time() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+impl Shard {
+    /// Purge all expired keys and return the `Instant` at which the **next**
+    /// key will expire. The background task will sleep until this instant.
+    fn purge_expired_keys(&self) -> Option<Instant> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.shutdown {
+            // The database is shutting down. All handles to the shared state
+            // have dropped. The background task should exit.
+            return None;
+        }
+
+        let now = Instant::now();
+
+        while let Some((when, key)) = state.expirations.first() {
+            if *when > now {
+                // Done purging, `when` is the instant at which the next key
+                // expires. The worker task will wait until this instant.
+                return Some(*when);
+            }
+
+            let key = key.clone();
+            state.remove(&key);
+        }
+
+        None
+    }
+
+    /// Returns `true` if the database is shutting down
+    fn is_shutdown(&self) -> bool {
+        self.state.lock().unwrap().shutdown
+    }
+
+    /// Wakes the background task up if `notify` is set.
+    fn notify_if(&self, notify: bool) {
+        if notify {
+            self.background_task.notify_one();
+        }
+    }
+}
+
+impl State {
+    /// Returns the entry of `key`, removing it first if it has expired.
+    fn entry(&mut self, key: &str) -> Option<&mut Entry> {
+        let expired = match self.entries.get(key)?.expires_at {
+            Some(when) => when <= Instant::now(),
+            None => false,
+        };
+
+        if expired {
+            self.remove(key);
+            return None;
+        }
+
+        self.entries.get_mut(key)
+    }
+
+    /// Returns the list held by `key`, or `None` if there is no such key.
+    fn list(&mut self, key: &str) -> Result<Option<&mut VecDeque<Bytes>>, WrongType> {
+        match self.entry(key) {
+            Some(Entry {
+                value: Value::List(list),
+                ..
+            }) => Ok(Some(list)),
+            Some(_) => Err(WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the list held by `key`, creating an empty one if there is no
+    /// such key.
+    fn list_or_default(&mut self, key: &str) -> Result<&mut VecDeque<Bytes>, WrongType> {
+        // Removes the key first if it has expired.
+        self.entry(key);
+
+        let entry = self
+            .entries
+            .entry(key.to_string())
+            .or_insert_with(|| Entry {
+                value: Value::List(VecDeque::new()),
+                expires_at: None,
+            });
+
+        match &mut entry.value {
+            Value::List(list) => Ok(list),
+            _ => Err(WrongType),
+        }
+    }
+
+    /// Removes `key` if it holds an empty list. Like Redis, a list is deleted
+    /// as soon as its last value is removed.
+    fn remove_if_empty(&mut self, key: &str) {
+        if let Some(Entry {
+            value: Value::List(list),
+            ..
+        }) = self.entries.get(key)
+        {
+            if list.is_empty() {
+                self.remove(key);
+            }
+        }
+    }
+
+    /// Removes `key` and its expiration.
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+
+        if let Some(when) = entry.expires_at {
+            self.expirations.remove(&(when, key.to_string()));
+        }
+
+        Some(entry)
+    }
+
+    /// Applies `expiry` to the existing entry of `key`.
+    ///
+    /// Returns `true` if the key is now the next one to expire, in which case
+    /// the background task must be notified so it does not sleep past it.
+    fn set_expiry(&mut self, key: &str, expiry: Expiry) -> bool {
+        let when = match expiry {
+            Expiry::Keep => return false,
+            Expiry::Persist => None,
+            Expiry::At(when) => Some(when),
+        };
+
+        let entry = match self.entries.get_mut(key) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        let previous = std::mem::replace(&mut entry.expires_at, when);
+
+        if let Some(previous) = previous {
+            self.expirations.remove(&(previous, key.to_string()));
+        }
+
+        match when {
+            Some(when) => {
+                let notify = self
+                    .expirations
+                    .first()
+                    .map(|(next, _)| *next > when)
+                    .unwrap_or(true);
+
+                self.expirations.insert((when, key.to_string()));
+                notify
+            }
+            None => false,
+        }
+    }
+}
+
+impl Waiter {
+    /// Waits for a value to be pushed to one of the keys, returning the key
+    /// along with the value. For a client blocked by `Db::lmove_or_block`,
+    /// the value has been moved by then, or the error preventing it from
+    /// being moved is returned.
+    ///
+    /// This is cancel safe: if the future is dropped, the value is received by
+    /// the next call.
+    pub async fn recv(&mut self) -> Result<(String, Bytes), WrongType> {
+        // The sender stays in the slot this waiter shares until it is taken
+        // to serve the client, so the channel is never closed without a value.
+        (&mut self.rx)
+            .await
+            .expect("blocked client dropped without being served")
+    }
+
+    /// Stops waiting, returning what the client was served if it was served
+    /// in the meantime, e.g. when its timeout expires at the same time.
+    ///
+    /// Once this returns, the client is not served anymore. A value returned
+    /// is the caller's: it is not pushed back when the `Waiter` is dropped.
+    pub fn cancel(&mut self) -> Option<Result<(String, Bytes), WrongType>> {
+        // Values are sent with the slot locked, so once the sender is taken
+        // here, whatever was sent is in the receiver.
+        self.slot.lock().unwrap().take();
+        self.rx.try_recv().ok()
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        // Once the sender is taken, no value can be sent anymore. If it was
+        // already taken, a value was popped for this client.
+        let served = self.slot.lock().unwrap().take().is_none();
+
+        for key in &self.keys {
+            let shard = &self.shards[shard_index(&self.shards, key)];
+            let mut state = shard.state.lock().unwrap();
+
+            if let Some(queue) = state.blocked.get_mut(key) {
+                queue.retain(|blocked| !Arc::ptr_eq(&blocked.slot, &self.slot));
+
+                if queue.is_empty() {
+                    state.blocked.remove(key);
+                }
+            }
+        }
+
+        // A value moved to a destination stays there, the move is done.
+        if !served || self.moves {
+            return;
+        }
+
+        // The value was not received, so push it back where it was popped
+        // from, serving the next client blocked on the key.
+        if let Ok(Ok((key, value))) = self.rx.try_recv() {
+            let mut states = lock_for_serving(&self.shards, &[&key]);
+            let state = locked_state(&mut states, shard_index(&self.shards, &key));
+
+            // The key may hold another kind of value by now, in which case
+            // there is nowhere to put the value back.
+            if let Ok(list) = state.list_or_default(&key) {
+                match self.end {
+                    End::Left => list.push_front(value),
+                    End::Right => list.push_back(value),
+                }
+            }
+
+            state.remove_if_empty(&key);
+            serve_blocked(&self.shards, states, &[&key]);
+        }
+    }
+}
+
+/// Routine executed by the background task of each shard.
+///
+/// Wait to be notified. On notification, purge any expired keys from the
+/// shard. Once `shutdown` is set, terminate the task.
+async fn purge_expired_tasks(shard: Arc<Shard>) {
+    // If the shutdown flag is set, then the task should exit.
+    while !shard.is_shutdown() {
+        // Purge all keys that are expired. The function returns the instant at
+        // which the **next** key will expire. The worker should wait until the
+        // instant has passed then purge again.
+        if let Some(when) = shard.purge_expired_keys() {
+            // Wait until the next key expires **or** until the background task
+            // is notified. If the task is notified, then it must reload its
+            // state as new keys have been set to expire early.
+            tokio::select! {
+                _ = time::sleep_until(when) => {}
+                _ = shard.background_task.notified() => {}
+            }
+        } else {
+            // There are no keys expiring in the future. Wait until the task is
+            // notified.
+            shard.background_task.notified().await;
+        }
+    }
+}
@@ -1,19 +1,115 @@
-use bytes::{Buf, Bytes};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::convert::TryInto;
 use std::fmt;
 use std::io::Cursor;
-use std::num::TryFromIntError;
-use std::string::FromUtf8Error;
 
 /// A Frame in the Redis protocol.
-#[derive(Debug, Clone)]
+///
+/// The first seven variants are shared by RESP2 and RESP3. The remaining ones
+/// only exist in RESP3 and are downgraded to their closest RESP2 equivalent
+/// when written to a connection that has not negotiated RESP3 with `HELLO`.
+///
+/// RESP2 has two nulls, a null bulk string and a null array, which RESP3
+/// merged into one. `Null` is written as the former and `NullArray` as the
+/// latter, for replies where Redis uses a null array. Both are read back as
+/// `Null`.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Frame {
     Simple(String),
     Error(String),
     Integer(i64),
     Bulk(Bytes),
     Null,
+    NullArray,
     Array(Vec<Frame>),
+    Map(Vec<(Frame, Frame)>),
+    Set(Vec<Frame>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    Verbatim(String, Bytes),
+    Push(Vec<Frame>),
+    Attribute(Vec<(Frame, Frame)>),
+}
+
+/// The version of the Redis protocol spoken on a connection.
+///
+/// Every connection starts out speaking RESP2 and may switch to RESP3 by
+/// sending `HELLO 3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Protocol {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+impl Protocol {
+    /// Returns the protocol for the version number given to `HELLO`.
+    pub fn from_version(version: i64) -> Option<Protocol> {
+        match version {
+            2 => Some(Protocol::Resp2),
+            3 => Some(Protocol::Resp3),
+            _ => None,
+        }
+    }
+
+    /// Returns the version number reported by `HELLO`.
+    pub fn version(self) -> i64 {
+        match self {
+            Protocol::Resp2 => 2,
+            Protocol::Resp3 => 3,
+        }
+    }
+}
+
+/// Limits applied while checking incoming frames.
+///
+/// Without them, a client could declare a huge bulk string or array length and
+/// make the connection buffer without bound while waiting for the data, or
+/// nest arrays deep enough to overflow the stack of the recursive `check` and
+/// `parse`. Frames exceeding a limit are rejected with a protocol error.
+///
+/// The defaults match the ones used by Redis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolLimits {
+    /// Maximum length of a bulk string, Redis' `proto-max-bulk-len`.
+    pub max_bulk_len: usize,
+
+    /// Maximum number of elements in an array, set or push frame, or of
+    /// entries in a map or attribute frame.
+    pub max_multibulk_len: usize,
+
+    /// Maximum number of aggregate frames nested within each other. A plain
+    /// command array has a depth of 1.
+    pub max_depth: usize,
+
+    /// Maximum length of an inline command and of any other line, such as a
+    /// simple string or a length header.
+    pub max_inline_len: usize,
+}
+
+impl ProtocolLimits {
+    /// Returns limits that never reject a frame. Used when parsing a message
+    /// that was already checked.
+    fn unlimited() -> ProtocolLimits {
+        ProtocolLimits {
+            max_bulk_len: usize::MAX,
+            max_multibulk_len: usize::MAX,
+            max_depth: usize::MAX,
+            max_inline_len: usize::MAX,
+        }
+    }
+}
+
+impl Default for ProtocolLimits {
+    fn default() -> ProtocolLimits {
+        ProtocolLimits {
+            max_bulk_len: 512 * 1024 * 1024,
+            max_multibulk_len: 1024 * 1024,
+            max_depth: 64,
+            max_inline_len: 64 * 1024,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -22,12 +118,70 @@ pub enum Error {
     Incomplete,
 
     /// Invalid message encoding.
-    Other(crate::Error),
+    Protocol(ProtocolError),
+}
+
+/// A violation of the Redis protocol found while decoding a frame.
+///
+/// Every case records where it happened: `offset` is the position of the
+/// offending frame's first byte, counted from the start of the decoded input,
+/// and `path` holds the index of the element at each level of nested
+/// aggregates leading to that frame. The path is empty for a top-level frame.
+/// Entries of maps and attributes count as two elements, their key and their
+/// value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolError {
+    /// A frame starts with a byte that is not a RESP type byte.
+    InvalidTypeByte {
+        byte: u8,
+        offset: u64,
+        path: Vec<usize>,
+    },
+
+    /// A length header is not a number, or is negative.
+    BadLength { offset: u64, path: Vec<usize> },
+
+    /// A string that must be text is not valid UTF-8.
+    InvalidUtf8 { offset: u64, path: Vec<usize> },
+
+    /// A value does not follow the format of its type, such as an integer
+    /// that is not a number or a bulk string not terminated by "\r\n".
+    InvalidFormat { offset: u64, path: Vec<usize> },
+
+    /// An inline command has a quoted argument that is not closed properly.
+    UnbalancedQuotes { offset: u64, path: Vec<usize> },
+
+    /// A frame exceeds one of the `ProtocolLimits`.
+    LimitExceeded {
+        limit: Limit,
+        offset: u64,
+        path: Vec<usize>,
+    },
+
+    /// The stream ended in the middle of a frame.
+    UnexpectedEof { offset: u64, path: Vec<usize> },
+}
+
+/// One of the `ProtocolLimits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    BulkLen,
+    MultibulkLen,
+    Depth,
+    InlineLen,
+}
+
+/// Reason a value failed to decode, before where it happened is known.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Invalid {
+    Utf8,
+    Format,
+    UnbalancedQuotes,
 }
 
 impl Frame {
     /// Returns an empty array
-    pub(crate) fn array() -> Frame {
+    pub fn array() -> Frame {
         Frame::Array(vec![])
     }
 
@@ -36,7 +190,7 @@ impl Frame {
     /// # Panics
     ///
     /// panics if `self` is not an array.
-    pub(crate) fn push_bulk(&mut self, bytes: Bytes) {
+    pub fn push_bulk(&mut self, bytes: Bytes) {
         match self {
             Frame::Array(vec) => vec.push(Frame::Bulk(bytes)),
             _ => panic!("not an array frame"),
@@ -48,122 +202,301 @@ impl Frame {
     /// # Panics
     ///
     /// panics if `self` is not an array
-    pub(crate) fn push_int(&mut self, value: i64) {
+    pub fn push_int(&mut self, value: i64) {
         match self {
             Frame::Array(vec) => vec.push(Frame::Integer(value)),
             _ => panic!("not an array frame"),
         }
     }
 
-    /// Checks if an entire message can be decoded from `src`.
+    /// Checks if an entire message can be decoded from `src`, using the
+    /// default `ProtocolLimits`.
+    ///
+    /// Besides RESP frames, a message may be an inline command: a plain line
+    /// of space-separated arguments as typed into `nc` or telnet. Inline
+    /// commands are recognized by a first byte that is not a RESP type byte.
     pub fn check(src: &mut Cursor<&[u8]>) -> Result<(), Error> {
+        Frame::check_with_limits(src, &ProtocolLimits::default())
+    }
+
+    /// Checks if an entire message can be decoded from `src` without exceeding
+    /// `limits`.
+    ///
+    /// Limits are enforced as soon as the offending length header or line is
+    /// seen, before the data it announces has been received.
+    pub fn check_with_limits(
+        src: &mut Cursor<&[u8]>,
+        limits: &ProtocolLimits,
+    ) -> Result<(), Error> {
+        if is_inline(src)? {
+            let offset = src.position();
+            let line = get_inline_line(src, limits.max_inline_len)?;
+
+            // Parse the arguments now so that unbalanced quotes are reported
+            // by `check` rather than `parse`.
+            split_args(line).map_err(|invalid| invalid.at(offset, vec![]))?;
+
+            return Ok(());
+        }
+
+        Frame::check_value(src, limits, &mut vec![])
+    }
+
+    /// Checks a single RESP frame, recursing into aggregates. `path` leads
+    /// from the top-level frame to this one.
+    fn check_value(
+        src: &mut Cursor<&[u8]>,
+        limits: &ProtocolLimits,
+        path: &mut Vec<usize>,
+    ) -> Result<(), Error> {
+        let offset = src.position();
+
         match get_u8(src)? {
-            // Simple strings and Errors.
-            b'+' | b'-' => {
-                get_line(src)?;
+            prefix @ (b'+' | b'-' | b':' | b'_' | b'#' | b',' | b'(') => {
+                let line = get_limited_line(src, limits, offset, path)?;
+
+                // Decode the value to report malformed ones. Besides bulk
+                // strings, frames are small so this is cheap.
+                line_frame(prefix, line).map_err(|invalid| invalid.at(offset, path.clone()))?;
+
                 Ok(())
             }
-            b':' => {
-                get_decimal(src)?;
+            // Verbatim strings and blob errors are framed like bulk strings.
+            prefix @ (b'$' | b'=' | b'!') => {
+                let len = get_length(src, limits, offset, path)?;
+
+                // A null bulk string "$-1\r\n"
+                let len = match len {
+                    Some(len) => len,
+                    None if prefix == b'$' => return Ok(()),
+                    None => return Err(ProtocolError::bad_length(offset, path)),
+                };
+
+                if len > limits.max_bulk_len {
+                    return Err(ProtocolError::limit(Limit::BulkLen, offset, path));
+                }
+
+                // Skip that number of bytes + 2 (\r\n). With limits high
+                // enough, the length may be too close to `usize::MAX` for that.
+                let start = src.position();
+                let n = len
+                    .checked_add(2)
+                    .ok_or_else(|| ProtocolError::bad_length(offset, path))?;
+                skip(src, n)?;
+
+                if &src.get_ref()[start as usize + len..src.position() as usize] != b"\r\n" {
+                    return Err(Invalid::Format.at(offset, path.clone()).into());
+                }
+
                 Ok(())
             }
-            b'$' => {
-                if b'-' == peek_u8(src)? {
-                    // Skip "-1\r\n"
-                    skip(src, 4)
-                } else {
-                    // Read the length of the bulk string.
-                    let len: usize = get_decimal(src)?.try_into()?;
+            prefix @ (b'*' | b'~' | b'>' | b'%' | b'|') => {
+                let len = get_length(src, limits, offset, path)?;
+
+                // A RESP2 null array "*-1\r\n"
+                let len = match len {
+                    Some(len) => len,
+                    None if prefix == b'*' => return Ok(()),
+                    None => return Err(ProtocolError::bad_length(offset, path)),
+                };
 
-                    // Skip that number of bytes + 2 (\r\n)
-                    skip(src, len + 2)
+                if len > limits.max_multibulk_len {
+                    return Err(ProtocolError::limit(Limit::MultibulkLen, offset, path));
                 }
-            }
-            b'*' => {
-                // Read the length of the array.
-                let len = get_decimal(src)?;
 
-                // Check the `len` number of frames that should appear after the
+                if path.len() >= limits.max_depth {
+                    return Err(ProtocolError::limit(Limit::Depth, offset, path));
+                }
+
+                // Maps and attributes are followed by `len` key-value pairs.
+                let frames = if matches!(prefix, b'%' | b'|') {
+                    len * 2
+                } else {
+                    len
+                };
+
+                // Check the number of frames that should appear after the
                 // given length.
-                for _ in 0..len {
-                    Frame::check(src)?;
+                for i in 0..frames {
+                    path.push(i);
+                    Frame::check_value(src, limits, path)?;
+                    path.pop();
                 }
 
                 Ok(())
             }
-            actual => Err(format!("protocol error; invalid frame type byte `{}`", actual).into()),
+            byte => Err(ProtocolError::InvalidTypeByte {
+                byte,
+                offset,
+                path: path.clone(),
+            }
+            .into()),
         }
     }
 
     /// The message has already been validated with `check`.
-    pub fn parse(src: &mut Cursor<&[u8]>) -> Result<Frame, Error> {
-        match get_u8(src)? {
-            b'+' => {
-                // Read the line into a `Vec<u8>`
-                let line = get_line(src)?.to_vec();
+    ///
+    /// `src` holds the bytes of the message, typically split off a
+    /// connection's read buffer with `BytesMut::split_to` and frozen. Bulk
+    /// strings in the returned frame are slices of `src` sharing its memory,
+    /// so their payload is never copied. The parsed bytes are consumed from
+    /// `src`.
+    ///
+    /// Inline commands are returned as an array of bulk strings, the same
+    /// shape as a command sent by a regular client.
+    pub fn parse(src: &mut Bytes) -> Result<Frame, Error> {
+        let buf = src.clone();
+        let mut cursor = Cursor::new(&buf[..]);
 
-                // Convert the line to a String
-                let string = String::from_utf8(line)?;
+        let frame = if is_inline(&mut cursor)? {
+            // The line length was already enforced by `check`.
+            let args = split_args(get_inline_line(&mut cursor, usize::MAX)?)
+                .map_err(|invalid| invalid.at(0, vec![]))?;
 
-                Ok(Frame::Simple(string))
-            }
-            b'-' => {
-                // Read the line into a `Vec<u8>`
-                let line = get_line(src)?.to_vec();
+            Frame::Array(args.into_iter().map(Frame::Bulk).collect())
+        } else {
+            Frame::parse_value(&mut cursor, &buf, &mut vec![])?
+        };
+
+        src.advance(cursor.position() as usize);
+
+        Ok(frame)
+    }
 
-                // Convert the line to a String
-                let string = String::from_utf8(line)?;
+    /// Parses a single RESP frame, recursing into aggregates. `buf` is the
+    /// buffer `src` reads from, bulk strings are sliced out of it. `path`
+    /// leads from the top-level frame to this one.
+    fn parse_value(
+        src: &mut Cursor<&[u8]>,
+        buf: &Bytes,
+        path: &mut Vec<usize>,
+    ) -> Result<Frame, Error> {
+        let offset = src.position();
+        let limits = ProtocolLimits::unlimited();
 
-                Ok(Frame::Error(string))
+        match get_u8(src)? {
+            prefix @ (b'+' | b'-' | b':' | b'_' | b'#' | b',' | b'(') => {
+                line_frame(prefix, get_line(src)?)
+                    .map_err(|invalid| invalid.at(offset, path.clone()).into())
             }
-            b':' => {
-                let len = get_decimal(src)?;
-                Ok(Frame::Integer(len))
+            prefix @ (b'$' | b'=' | b'!') => {
+                let len = match get_length(src, &limits, offset, path)? {
+                    Some(len) => len,
+                    // A null bulk string "$-1\r\n"
+                    None if prefix == b'$' => return Ok(Frame::Null),
+                    None => return Err(ProtocolError::bad_length(offset, path)),
+                };
+
+                blob_frame(prefix, get_blob(src, buf, len, offset, path)?)
+                    .map_err(|invalid| invalid.at(offset, path.clone()).into())
             }
-            b'$' => {
-                if b'-' == peek_u8(src)? {
-                    // A null bulk string "-1\r\n"
+            prefix @ (b'*' | b'~' | b'>' | b'%' | b'|') => {
+                let len = match get_length(src, &limits, offset, path)? {
+                    Some(len) => len,
+                    // A RESP2 null array "*-1\r\n"
+                    None if prefix == b'*' => return Ok(Frame::Null),
+                    None => return Err(ProtocolError::bad_length(offset, path)),
+                };
+
+                if matches!(prefix, b'%' | b'|') {
+                    let mut out = Vec::with_capacity(len);
+
+                    for i in 0..len {
+                        path.push(2 * i);
+                        let key = Frame::parse_value(src, buf, path)?;
+                        path.pop();
 
-                    let line = get_line(src)?;
+                        path.push(2 * i + 1);
+                        let value = Frame::parse_value(src, buf, path)?;
+                        path.pop();
 
-                    if line != b"-1" {
-                        return Err("protocol error; invalid frame format".into());
+                        out.push((key, value));
                     }
 
-                    Ok(Frame::Null)
+                    Ok(pairs_frame(prefix, out))
                 } else {
-                    // Read the bulk string.
-                    let len = get_decimal(src)?.try_into()?;
-                    let n = len + 2;
+                    let mut out = Vec::with_capacity(len);
 
-                    if src.remaining() < n {
-                        return Err(Error::Incomplete);
+                    for i in 0..len {
+                        path.push(i);
+                        out.push(Frame::parse_value(src, buf, path)?);
+                        path.pop();
                     }
 
-                    let data = Bytes::copy_from_slice(&src.get_ref()[..len]);
+                    Ok(aggregate_frame(prefix, out))
+                }
+            }
+            byte => Err(ProtocolError::InvalidTypeByte {
+                byte,
+                offset,
+                path: path.clone(),
+            }
+            .into()),
+        }
+    }
 
-                    // Skip that number of bytes + 2 (\r\n).
-                    skip(src, n)?;
+    /// Encodes the frame into `dst` using the given protocol version.
+    ///
+    /// Frames that only exist in RESP3 are downgraded to their RESP2
+    /// equivalent when `protocol` is `Resp2`. Encoding is synchronous, which
+    /// allows aggregates to be nested arbitrarily deep.
+    pub fn encode(&self, dst: &mut BytesMut, protocol: Protocol) {
+        let resp3 = protocol == Protocol::Resp3;
 
-                    Ok(Frame::Bulk(data))
-                }
+        match self {
+            Frame::Simple(val) => put_line(dst, b'+', val.as_bytes()),
+            Frame::Error(val) => put_line(dst, b'-', val.as_bytes()),
+            Frame::Integer(val) => put_decimal(dst, b':', *val),
+            Frame::Bulk(val) => put_blob(dst, b'$', val),
+            Frame::Null | Frame::NullArray if resp3 => dst.put_slice(b"_\r\n"),
+            Frame::Null => dst.put_slice(b"$-1\r\n"),
+            Frame::NullArray => dst.put_slice(b"*-1\r\n"),
+            Frame::Array(val) => put_frames(dst, b'*', val, protocol),
+            Frame::Set(val) => put_frames(dst, if resp3 { b'~' } else { b'*' }, val, protocol),
+            Frame::Push(val) => put_frames(dst, if resp3 { b'>' } else { b'*' }, val, protocol),
+            Frame::Map(val) if resp3 => put_pairs(dst, b'%', val, protocol),
+            Frame::Map(val) => {
+                // RESP2 has no map type, the pairs are flattened into an array.
+                let flat: Vec<&Frame> = val.iter().flat_map(|(key, value)| [key, value]).collect();
+                put_frames(dst, b'*', flat, protocol);
             }
-            b'*' => {
-                let len = get_decimal(src)?.try_into()?;
-                let mut out = Vec::with_capacity(len);
+            Frame::Attribute(val) if resp3 => put_pairs(dst, b'|', val, protocol),
+            // Attributes are auxiliary data a RESP2 client would not
+            // understand, so they are dropped entirely.
+            Frame::Attribute(_) => {}
+            Frame::Double(val) => {
+                let val = double_text(*val);
 
-                for _ in 0..len {
-                    out.push(Frame::parse(src)?);
+                if resp3 {
+                    put_line(dst, b',', val.as_bytes());
+                } else {
+                    put_blob(dst, b'$', val.as_bytes());
                 }
-
-                Ok(Frame::Array(out))
             }
-            _ => unimplemented!(),
+            Frame::Boolean(val) if resp3 => put_line(dst, b'#', if *val { b"t" } else { b"f" }),
+            Frame::Boolean(val) => put_decimal(dst, b':', *val as i64),
+            Frame::BigNumber(val) if resp3 => put_line(dst, b'(', val.as_bytes()),
+            Frame::BigNumber(val) => put_blob(dst, b'$', val.as_bytes()),
+            Frame::Verbatim(format, val) if resp3 => {
+                put_decimal(dst, b'=', (format.len() + 1 + val.len()) as i64);
+                dst.put_slice(format.as_bytes());
+                dst.put_u8(b':');
+                dst.put_slice(val);
+                dst.put_slice(b"\r\n");
+            }
+            Frame::Verbatim(_, val) => put_blob(dst, b'$', val),
         }
     }
 
+    /// Returns a value displaying the frame the way `redis-cli --raw` does:
+    /// strings are written as they are, without type hints, and the elements
+    /// of aggregates are written one per line.
+    pub fn raw(&self) -> Raw<'_> {
+        Raw(self)
+    }
+
     /// Converts the frame to an "unexpected frame" error
-    pub(crate) fn to_error(&self) -> crate::Error {
+    pub fn to_error(&self) -> crate::Error {
         format!("unexpected frame: {}", self).into()
     }
 }
@@ -178,30 +511,470 @@ impl PartialEq<&str> for Frame {
     }
 }
 
+/// Formats the frame the way `redis-cli` prints replies on a terminal.
+///
+/// Strings are quoted with non-printable bytes escaped, other values carry a
+/// type hint such as `(integer)`, and aggregates list their elements one per
+/// line, numbered and indented according to their nesting. Use `Frame::raw`
+/// for the unadorned output `redis-cli --raw` produces.
 impl fmt::Display for Frame {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        use std::str;
+        fmt_pretty(self, fmt, "")
+    }
+}
 
-        match self {
-            Frame::Simple(response) => response.fmt(fmt),
-            Frame::Error(msg) => write!(fmt, "error: {}", msg),
-            Frame::Integer(num) => num.fmt(fmt),
-            Frame::Bulk(msg) => match str::from_utf8(msg) {
-                Ok(string) => string.fmt(fmt),
-                Err(_) => write!(fmt, "{:?}", msg),
-            },
-            Frame::Null => "(nil)".fmt(fmt),
-            Frame::Array(parts) => {
-                for (i, part) in parts.iter().enumerate() {
+/// Displays a frame the way `redis-cli --raw` prints replies.
+///
+/// Returned by `Frame::raw`.
+#[derive(Debug, Clone, Copy)]
+pub struct Raw<'a>(&'a Frame);
+
+impl fmt::Display for Raw<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Frame::Simple(val) | Frame::Error(val) | Frame::BigNumber(val) => fmt.write_str(val),
+            Frame::Integer(val) => val.fmt(fmt),
+            Frame::Bulk(val) | Frame::Verbatim(_, val) => {
+                fmt.write_str(&String::from_utf8_lossy(val))
+            }
+            Frame::Null | Frame::NullArray => Ok(()),
+            Frame::Array(frames) | Frame::Set(frames) | Frame::Push(frames) => {
+                for (i, frame) in frames.iter().enumerate() {
                     if i > 0 {
-                        write!(fmt, " ")?;
-                        part.fmt(fmt)?;
+                        fmt.write_str("\n")?;
                     }
+
+                    frame.raw().fmt(fmt)?;
                 }
 
                 Ok(())
             }
+            Frame::Map(pairs) | Frame::Attribute(pairs) => {
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        fmt.write_str("\n")?;
+                    }
+
+                    write!(fmt, "{} {}", key.raw(), value.raw())?;
+                }
+
+                Ok(())
+            }
+            Frame::Double(val) => fmt.write_str(&double_text(*val)),
+            Frame::Boolean(val) => write!(fmt, "({})", val),
+        }
+    }
+}
+
+/// Writes `frame` in the format of `redis-cli`. Lines after the first one are
+/// indented with `prefix`.
+fn fmt_pretty(frame: &Frame, fmt: &mut fmt::Formatter, prefix: &str) -> fmt::Result {
+    fn single(frame: &Frame) -> (&Frame, Option<&Frame>) {
+        (frame, None)
+    }
+
+    fn pair((key, value): &(Frame, Frame)) -> (&Frame, Option<&Frame>) {
+        (key, Some(value))
+    }
+
+    // Aggregates number their entries, followed by a separator telling their
+    // type apart.
+    let (entries, sep, kind): (Vec<_>, _, _) = match frame {
+        Frame::Array(frames) => (frames.iter().map(single).collect(), ')', "array"),
+        Frame::Set(frames) => (frames.iter().map(single).collect(), '~', "set"),
+        Frame::Push(frames) => (frames.iter().map(single).collect(), ')', "push"),
+        Frame::Map(pairs) => (pairs.iter().map(pair).collect(), '#', "hash"),
+        Frame::Attribute(pairs) => (pairs.iter().map(pair).collect(), '|', "attribute"),
+        Frame::Simple(val) => return fmt.write_str(val),
+        Frame::Error(val) => return write!(fmt, "(error) {}", val),
+        Frame::Integer(val) => return write!(fmt, "(integer) {}", val),
+        Frame::Bulk(val) => return fmt_quoted(val, fmt),
+        Frame::Null | Frame::NullArray => return fmt.write_str("(nil)"),
+        Frame::Double(val) => return write!(fmt, "(double) {}", double_text(*val)),
+        Frame::Boolean(val) => return write!(fmt, "({})", val),
+        Frame::BigNumber(val) => return write!(fmt, "(big number) {}", val),
+        // Verbatim strings are meant to be shown to the user as they are.
+        Frame::Verbatim(_, val) => return fmt.write_str(&String::from_utf8_lossy(val)),
+    };
+
+    if entries.is_empty() {
+        return write!(fmt, "(empty {})", kind);
+    }
+
+    // Indices are aligned to the right, and nested entries are indented to
+    // start after the index of their parent.
+    let width = entries.len().to_string().len();
+    let nested = format!("{}{:width$}", prefix, "", width = width + 2);
+
+    for (i, (key, value)) in entries.into_iter().enumerate() {
+        // The index of the first entry goes on the line of the parent's
+        // index, which is already indented.
+        if i > 0 {
+            write!(fmt, "\n{}", prefix)?;
+        }
+
+        write!(fmt, "{:>width$}{} ", i + 1, sep, width = width)?;
+        fmt_pretty(key, fmt, &nested)?;
+
+        // Map values are written after their key.
+        if let Some(value) = value {
+            fmt.write_str(" => ")?;
+            fmt_pretty(value, fmt, &nested)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `val` in double quotes, escaping quotes, backslashes and
+/// non-printable bytes.
+fn fmt_quoted(val: &[u8], fmt: &mut fmt::Formatter) -> fmt::Result {
+    use std::fmt::Write;
+
+    fmt.write_char('"')?;
+
+    for &b in val {
+        match b {
+            b'\\' | b'"' => write!(fmt, "\\{}", b as char)?,
+            b'\n' => fmt.write_str("\\n")?,
+            b'\r' => fmt.write_str("\\r")?,
+            b'\t' => fmt.write_str("\\t")?,
+            0x07 => fmt.write_str("\\a")?,
+            0x08 => fmt.write_str("\\b")?,
+            b' '..=b'~' => fmt.write_char(b as char)?,
+            _ => write!(fmt, "\\x{:02x}", b)?,
+        }
+    }
+
+    fmt.write_char('"')
+}
+
+/// Returns the text of a double, as sent in RESP3 double frames.
+fn double_text(val: f64) -> String {
+    if val.is_nan() {
+        "nan".to_string()
+    } else if val.is_infinite() {
+        if val.is_sign_positive() {
+            "inf"
+        } else {
+            "-inf"
         }
+        .to_string()
+    } else {
+        val.to_string()
+    }
+}
+
+/// Builds a frame from the line following one of the single-line type bytes:
+/// `+`, `-`, `:`, `_`, `#`, `,` and `(`.
+pub(crate) fn line_frame(prefix: u8, line: &[u8]) -> Result<Frame, Invalid> {
+    use atoi::atoi;
+
+    match prefix {
+        b'+' => Ok(Frame::Simple(utf8(line)?)),
+        b'-' => Ok(Frame::Error(utf8(line)?)),
+        b':' => atoi::<i64>(line).map(Frame::Integer).ok_or(Invalid::Format),
+        b'_' if line.is_empty() => Ok(Frame::Null),
+        b'#' if line == b"t" => Ok(Frame::Boolean(true)),
+        b'#' if line == b"f" => Ok(Frame::Boolean(false)),
+        b',' => {
+            // `f64::from_str` accepts the `inf`, `-inf` and `nan` spellings
+            // used by RESP3.
+            std::str::from_utf8(line)
+                .ok()
+                .and_then(|line| line.parse::<f64>().ok())
+                .map(Frame::Double)
+                .ok_or(Invalid::Format)
+        }
+        b'(' => {
+            let string = utf8(line)?;
+
+            // Only the characters are validated, the number itself is kept as
+            // a string since it may not fit in any native integer.
+            let digits = string.strip_prefix('-').unwrap_or(&string);
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(Invalid::Format);
+            }
+
+            Ok(Frame::BigNumber(string))
+        }
+        _ => Err(Invalid::Format),
+    }
+}
+
+/// Builds a frame from the payload following one of the length-prefixed type
+/// bytes: `$`, `=` and `!`.
+pub(crate) fn blob_frame(prefix: u8, data: Bytes) -> Result<Frame, Invalid> {
+    match prefix {
+        b'$' => Ok(Frame::Bulk(data)),
+        b'=' => {
+            // The payload starts with a three character format followed by a
+            // colon, e.g. "txt:" or "mkd:".
+            if data.len() < 4 || data[3] != b':' {
+                return Err(Invalid::Format);
+            }
+
+            let format = utf8(&data[..3])?;
+
+            Ok(Frame::Verbatim(format, data.slice(4..)))
+        }
+        // Blob errors carry the same information as simple errors.
+        _ => Ok(Frame::Error(utf8(&data)?)),
+    }
+}
+
+/// Converts a line to a `String`.
+fn utf8(line: &[u8]) -> Result<String, Invalid> {
+    String::from_utf8(line.to_vec()).map_err(|_| Invalid::Utf8)
+}
+
+/// Builds the aggregate frame for the `*`, `~` and `>` type bytes.
+pub(crate) fn aggregate_frame(prefix: u8, frames: Vec<Frame>) -> Frame {
+    match prefix {
+        b'~' => Frame::Set(frames),
+        b'>' => Frame::Push(frames),
+        _ => Frame::Array(frames),
+    }
+}
+
+/// Builds the aggregate frame for the `%` and `|` type bytes.
+pub(crate) fn pairs_frame(prefix: u8, pairs: Vec<(Frame, Frame)>) -> Frame {
+    match prefix {
+        b'|' => Frame::Attribute(pairs),
+        _ => Frame::Map(pairs),
+    }
+}
+
+/// Returns `true` if `b` starts a RESP frame.
+pub(crate) fn is_type_byte(b: u8) -> bool {
+    matches!(
+        b,
+        b'+' | b'-'
+            | b':'
+            | b'$'
+            | b'*'
+            | b'%'
+            | b'~'
+            | b'>'
+            | b'|'
+            | b'_'
+            | b'#'
+            | b','
+            | b'('
+            | b'='
+            | b'!'
+    )
+}
+
+/// Returns `true` if the next message is an inline command rather than a RESP
+/// frame.
+fn is_inline(src: &mut Cursor<&[u8]>) -> Result<bool, Error> {
+    Ok(!is_type_byte(peek_u8(src)?))
+}
+
+/// Reads an inline command line. Like Redis, a bare "\n" is accepted as the
+/// line terminator, as that is what `nc` sends by default.
+///
+/// The line may not exceed `max_len` bytes, even when it has not been fully
+/// received yet. Otherwise, a client that never sends a newline could make
+/// the connection buffer without bound.
+fn get_inline_line<'a>(src: &mut Cursor<&'a [u8]>, max_len: usize) -> Result<&'a [u8], Error> {
+    let start = src.position() as usize;
+    let buf = &src.get_ref()[start..];
+
+    match buf.iter().position(|&b| b == b'\n') {
+        Some(end) if end <= max_len => {
+            src.set_position((start + end + 1) as u64);
+
+            let line = &buf[..end];
+            Ok(line.strip_suffix(b"\r").unwrap_or(line))
+        }
+        None if buf.len() <= max_len => Err(Error::Incomplete),
+        _ => Err(ProtocolError::limit(Limit::InlineLen, start as u64, &[])),
+    }
+}
+
+/// Splits an inline command line into its arguments.
+///
+/// Follows the rules of `redis-cli` and the Redis inline protocol: arguments
+/// are separated by whitespace and may be wrapped in double quotes, which
+/// support escape sequences such as `\n` and `\x41`, or in single quotes,
+/// which only support `\'`. A closing quote must be followed by whitespace
+/// or the end of the line.
+pub(crate) fn split_args(line: &[u8]) -> Result<Vec<Bytes>, Invalid> {
+    let mut args = vec![];
+    let mut i = 0;
+
+    loop {
+        // Skip blanks.
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+
+        if i == line.len() {
+            return Ok(args);
+        }
+
+        let mut arg = vec![];
+
+        match line[i] {
+            b'"' => {
+                i += 1;
+
+                loop {
+                    match line.get(i) {
+                        None => return Err(Invalid::UnbalancedQuotes),
+                        Some(b'\\') if is_hex_escape(&line[i..]) => {
+                            arg.push(hex_value(line[i + 2]) * 16 + hex_value(line[i + 3]));
+                            i += 4;
+                        }
+                        Some(b'\\') if i + 1 < line.len() => {
+                            arg.push(match line[i + 1] {
+                                b'n' => b'\n',
+                                b'r' => b'\r',
+                                b't' => b'\t',
+                                b'b' => 0x08,
+                                b'a' => 0x07,
+                                c => c,
+                            });
+                            i += 2;
+                        }
+                        Some(b'"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&c) => {
+                            arg.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            b'\'' => {
+                i += 1;
+
+                loop {
+                    match line.get(i) {
+                        None => return Err(Invalid::UnbalancedQuotes),
+                        Some(b'\\') if line.get(i + 1) == Some(&b'\'') => {
+                            arg.push(b'\'');
+                            i += 2;
+                        }
+                        Some(b'\'') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&c) => {
+                            arg.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            _ => {
+                while i < line.len() && !line[i].is_ascii_whitespace() {
+                    arg.push(line[i]);
+                    i += 1;
+                }
+            }
+        }
+
+        // A closing quote must be followed by a blank or the end of the line.
+        if i < line.len() && !line[i].is_ascii_whitespace() {
+            return Err(Invalid::UnbalancedQuotes);
+        }
+
+        args.push(Bytes::from(arg));
+    }
+}
+
+/// Returns `true` if `src` starts with a `\xHH` escape sequence.
+fn is_hex_escape(src: &[u8]) -> bool {
+    src.len() >= 4 && src[1] == b'x' && src[2].is_ascii_hexdigit() && src[3].is_ascii_hexdigit()
+}
+
+/// Returns the value of an ASCII hex digit.
+fn hex_value(c: u8) -> u8 {
+    match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'f' => c - b'a' + 10,
+        _ => c - b'A' + 10,
+    }
+}
+
+/// Reads a payload of `len` bytes, as used by bulk strings, verbatim strings
+/// and blob errors, and the trailing "\r\n". The payload is returned as a
+/// slice of `buf` without copying. `offset` and `path` locate the frame in
+/// errors.
+fn get_blob(
+    src: &mut Cursor<&[u8]>,
+    buf: &Bytes,
+    len: usize,
+    offset: u64,
+    path: &[usize],
+) -> Result<Bytes, Error> {
+    let start = src.position() as usize;
+    let n = len
+        .checked_add(2)
+        .ok_or_else(|| ProtocolError::bad_length(offset, path))?;
+
+    skip(src, n)?;
+
+    Ok(buf.slice(start..start + len))
+}
+
+/// Writes a type byte followed by a "\r\n" terminated line.
+fn put_line(dst: &mut BytesMut, prefix: u8, line: &[u8]) {
+    dst.put_u8(prefix);
+    // A "\r" or "\n" would end the line early, and what follows would be
+    // read as another frame. Like Redis does for errors, they are replaced
+    // with spaces.
+    dst.extend(line.iter().map(|&byte| match byte {
+        b'\r' | b'\n' => b' ',
+        byte => byte,
+    }));
+    dst.put_slice(b"\r\n");
+}
+
+/// Writes a type byte followed by a "\r\n" terminated decimal.
+fn put_decimal(dst: &mut BytesMut, prefix: u8, val: i64) {
+    put_line(dst, prefix, val.to_string().as_bytes());
+}
+
+/// Writes a length-prefixed payload such as a bulk string.
+fn put_blob(dst: &mut BytesMut, prefix: u8, val: &[u8]) {
+    put_decimal(dst, prefix, val.len() as i64);
+    dst.put_slice(val);
+    dst.put_slice(b"\r\n");
+}
+
+/// Writes an aggregate header followed by each of its elements.
+///
+/// In RESP2, attributes are not written, so they are not counted in the
+/// header either.
+fn put_frames<'a, I>(dst: &mut BytesMut, prefix: u8, frames: I, protocol: Protocol)
+where
+    I: IntoIterator<Item = &'a Frame>,
+{
+    let frames: Vec<&Frame> = frames
+        .into_iter()
+        .filter(|frame| protocol == Protocol::Resp3 || !matches!(frame, Frame::Attribute(_)))
+        .collect();
+
+    put_decimal(dst, prefix, frames.len() as i64);
+
+    for frame in frames {
+        frame.encode(dst, protocol);
+    }
+}
+
+/// Writes a map or attribute header followed by each key-value pair.
+fn put_pairs(dst: &mut BytesMut, prefix: u8, pairs: &[(Frame, Frame)], protocol: Protocol) {
+    put_decimal(dst, prefix, pairs.len() as i64);
+
+    for (key, value) in pairs {
+        key.encode(dst, protocol);
+        value.encode(dst, protocol);
     }
 }
 
@@ -232,7 +1005,7 @@ fn peek_u8(src: &mut Cursor<&[u8]>) -> Result<u8, Error> {
 
 /// Advances the internal buffer's position by `n`.
 fn skip(src: &mut Cursor<&[u8]>, n: usize) -> Result<(), Error> {
-    if !src.has_remaining() {
+    if src.remaining() < n {
         return Err(Error::Incomplete);
     }
 
@@ -240,13 +1013,50 @@ fn skip(src: &mut Cursor<&[u8]>, n: usize) -> Result<(), Error> {
     Ok(())
 }
 
-/// Read a new-line terminated decimal.
-fn get_decimal(src: &mut Cursor<&[u8]>) -> Result<i64, Error> {
-    use atoi::atoi;
+/// Read a line that may not exceed the limit on inline commands, even when it
+/// has not been fully received yet. `offset` and `path` locate the frame the
+/// line belongs to.
+fn get_limited_line<'a>(
+    src: &mut Cursor<&'a [u8]>,
+    limits: &ProtocolLimits,
+    offset: u64,
+    path: &[usize],
+) -> Result<&'a [u8], Error> {
+    let start = src.position() as usize;
+
+    match get_line(src) {
+        Ok(line) if line.len() > limits.max_inline_len => {
+            Err(ProtocolError::limit(Limit::InlineLen, offset, path))
+        }
+        // Without a "\r\n", all remaining bytes belong to the line.
+        Err(Error::Incomplete) if src.get_ref().len() - start > limits.max_inline_len => {
+            Err(ProtocolError::limit(Limit::InlineLen, offset, path))
+        }
+        res => res,
+    }
+}
 
-    let line = get_line(src)?;
+/// Read the length header of a bulk string or an aggregate. `None` is returned
+/// for a length of `-1`, which encodes a RESP2 null.
+fn get_length(
+    src: &mut Cursor<&[u8]>,
+    limits: &ProtocolLimits,
+    offset: u64,
+    path: &[usize],
+) -> Result<Option<usize>, Error> {
+    let line = get_limited_line(src, limits, offset, path)?;
+
+    length(line).map_err(|_| ProtocolError::bad_length(offset, path))
+}
 
-    atoi::<i64>(line).ok_or_else(|| "protocol error; invalid frame format".into())
+/// Parses a length header. `None` is returned for a length of `-1`, which
+/// encodes a RESP2 null.
+pub(crate) fn length(line: &[u8]) -> Result<Option<usize>, ()> {
+    match atoi::atoi::<i64>(line) {
+        Some(-1) => Ok(None),
+        Some(len) => len.try_into().map(Some).map_err(|_| ()),
+        None => Err(()),
+    }
 }
 
 fn get_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
@@ -268,37 +1078,123 @@ fn get_line<'a>(src: &mut Cursor<&'a [u8]>) -> Result<&'a [u8], Error> {
     Err(Error::Incomplete)
 }
 
-impl From<String> for Error {
-    fn from(src: String) -> Error {
-        Error::Other(src.into())
+impl From<ProtocolError> for Error {
+    fn from(src: ProtocolError) -> Error {
+        Error::Protocol(src)
     }
 }
 
-impl From<&str> for Error {
-    fn from(src: &str) -> Error {
-        src.to_string().into()
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Incomplete => "stream ended early".fmt(fmt),
+            Error::Protocol(err) => err.fmt(fmt),
+        }
     }
 }
 
-impl From<FromUtf8Error> for Error {
-    fn from(_src: FromUtf8Error) -> Error {
-        "protocol error; invalid frame format".into()
+impl ProtocolError {
+    pub(crate) fn bad_length(offset: u64, path: &[usize]) -> Error {
+        ProtocolError::BadLength {
+            offset,
+            path: path.to_vec(),
+        }
+        .into()
+    }
+
+    pub(crate) fn limit(limit: Limit, offset: u64, path: &[usize]) -> Error {
+        ProtocolError::LimitExceeded {
+            limit,
+            offset,
+            path: path.to_vec(),
+        }
+        .into()
+    }
+
+    /// Returns the position of the offending frame's first byte.
+    pub fn offset(&self) -> u64 {
+        match self {
+            ProtocolError::InvalidTypeByte { offset, .. }
+            | ProtocolError::BadLength { offset, .. }
+            | ProtocolError::InvalidUtf8 { offset, .. }
+            | ProtocolError::InvalidFormat { offset, .. }
+            | ProtocolError::UnbalancedQuotes { offset, .. }
+            | ProtocolError::LimitExceeded { offset, .. }
+            | ProtocolError::UnexpectedEof { offset, .. } => *offset,
+        }
+    }
+
+    /// Returns the path leading to the offending frame through nested
+    /// aggregates.
+    pub fn path(&self) -> &[usize] {
+        match self {
+            ProtocolError::InvalidTypeByte { path, .. }
+            | ProtocolError::BadLength { path, .. }
+            | ProtocolError::InvalidUtf8 { path, .. }
+            | ProtocolError::InvalidFormat { path, .. }
+            | ProtocolError::UnbalancedQuotes { path, .. }
+            | ProtocolError::LimitExceeded { path, .. }
+            | ProtocolError::UnexpectedEof { path, .. } => path,
+        }
+    }
+
+    /// Returns a short description of the error, as used in Redis' protocol
+    /// error replies.
+    fn reason(&self) -> String {
+        match self {
+            ProtocolError::InvalidTypeByte { byte, .. } => {
+                format!("invalid frame type byte '{}'", byte.escape_ascii())
+            }
+            ProtocolError::BadLength { .. } => "invalid length".to_string(),
+            ProtocolError::InvalidUtf8 { .. } => "invalid UTF-8 string".to_string(),
+            ProtocolError::InvalidFormat { .. } => "invalid frame format".to_string(),
+            ProtocolError::UnbalancedQuotes { .. } => "unbalanced quotes in request".to_string(),
+            ProtocolError::LimitExceeded { limit, .. } => match limit {
+                Limit::BulkLen => "invalid bulk length",
+                Limit::MultibulkLen => "invalid multibulk length",
+                Limit::Depth => "too many nested aggregates",
+                Limit::InlineLen => "too big inline request",
+            }
+            .to_string(),
+            ProtocolError::UnexpectedEof { .. } => "unexpected end of stream".to_string(),
+        }
     }
-}
 
-impl From<TryFromIntError> for Error {
-    fn from(_src: TryFromIntError) -> Error {
-        "protocol error; invalid frame format".into()
+    /// Returns the error reply sent to a client before closing its
+    /// connection, in the same format as Redis.
+    pub fn to_frame(&self) -> Frame {
+        Frame::Error(format!("ERR Protocol error: {}", self.reason()))
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for ProtocolError {}
 
-impl fmt::Display for Error {
+impl fmt::Display for ProtocolError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            fmt,
+            "protocol error; {} at byte {}",
+            self.reason(),
+            self.offset()
+        )?;
+
+        if !self.path().is_empty() {
+            write!(fmt, " in frame {:?}", self.path())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Invalid {
+    /// Turns the reason into a `ProtocolError` located at `offset` and `path`.
+    pub(crate) fn at(self, offset: u64, path: Vec<usize>) -> ProtocolError {
         match self {
-            Error::Incomplete => "stream ended early".fmt(fmt),
-            Error::Other(err) => err.fmt(fmt),
+            Invalid::Utf8 => ProtocolError::InvalidUtf8 { offset, path },
+            Invalid::Format => ProtocolError::InvalidFormat { offset, path },
+            Invalid::UnbalancedQuotes => ProtocolError::UnbalancedQuotes { offset, path },
         }
     }
 }
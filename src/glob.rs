@@ -0,0 +1,107 @@
+//! Glob-style pattern matching, with the same semantics as Redis.
+//!
+//! Patterns are used by `PSUBSCRIBE` and `PUBSUB CHANNELS`. They support:
+//!
+//! - `?`, matching any single byte,
+//! - `*`, matching any number of bytes, including none,
+//! - `[abc]`, matching one of the bytes listed, `[^abc]` any byte not listed,
+//!   and `[a-z]` any byte in the range,
+//! - `\`, escaping the special meaning of the byte that follows it.
+//!
+//! Like Redis, matching is done on bytes and is case sensitive. A malformed
+//! pattern is never an error: an unterminated `[` extends to the end of the
+//! pattern, and a trailing `\` matches itself.
+
+/// Returns `true` if `string` matches `pattern`.
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+
+    // Where to resume from if the rest of the pattern fails to match: right
+    // after the last `*` seen, and with that `*` matching one more byte than
+    // last tried. Only the last `*` ever needs to be retried, so matching
+    // takes linear space and no recursion.
+    let mut retry = None;
+
+    loop {
+        if pattern.get(p) == Some(&b'*') {
+            p += 1;
+            retry = Some((p, s));
+            continue;
+        }
+
+        if s == string.len() {
+            // The rest of the pattern would have to match nothing.
+            return p == pattern.len();
+        }
+
+        if p < pattern.len() {
+            let (matched, len) = match_one(&pattern[p..], string[s]);
+
+            if matched {
+                p += len;
+                s += 1;
+                continue;
+            }
+        }
+
+        match retry {
+            Some((retry_p, retry_s)) => {
+                retry = Some((retry_p, retry_s + 1));
+                p = retry_p;
+                s = retry_s + 1;
+            }
+            None => return false,
+        }
+    }
+}
+
+/// Matches `byte` against the element at the start of `pattern`, which is
+/// not a `*`.
+///
+/// Returns whether it matches, and the length of the element.
+fn match_one(pattern: &[u8], byte: u8) -> (bool, usize) {
+    match pattern[0] {
+        b'?' => (true, 1),
+        b'\\' if pattern.len() > 1 => (pattern[1] == byte, 2),
+        b'[' => match_class(pattern, byte),
+        literal => (literal == byte, 1),
+    }
+}
+
+/// Matches `byte` against the `[...]` class at the start of `pattern`.
+fn match_class(pattern: &[u8], byte: u8) -> (bool, usize) {
+    let negate = pattern.get(1) == Some(&b'^');
+    let mut i = if negate { 2 } else { 1 };
+    let mut matched = false;
+
+    loop {
+        match pattern.get(i) {
+            None => break,
+            Some(b']') => {
+                i += 1;
+                break;
+            }
+            Some(b'\\') if i + 1 < pattern.len() => {
+                matched |= pattern[i + 1] == byte;
+                i += 2;
+            }
+            Some(&start) if i + 2 < pattern.len() && pattern[i + 1] == b'-' => {
+                let end = pattern[i + 2];
+                let (low, high) = if start <= end {
+                    (start, end)
+                } else {
+                    (end, start)
+                };
+
+                matched |= (low..=high).contains(&byte);
+                i += 3;
+            }
+            Some(&literal) => {
+                matched |= literal == byte;
+                i += 1;
+            }
+        }
+    }
+
+    (matched != negate, i)
+}
@@ -1,10 +1,38 @@
 pub mod shard_db;
 
 pub mod frame;
-pub use frame::Frame;
+pub use frame::{Frame, Protocol, ProtocolError, ProtocolLimits};
+
+pub mod convert;
+pub use convert::{FromFrame, ToFrame};
+
+#[cfg(feature = "serde")]
+pub mod serde_frame;
+
+pub mod parser;
+pub use parser::Parser;
+
+pub mod codec;
+pub use codec::RespCodec;
 
 pub mod connection;
-pub use connection::Connection;
+pub use connection::{join, Connection, Timeouts};
+
+pub mod tls;
+
+pub mod shutdown;
+pub use shutdown::Shutdown;
+
+pub mod glob;
+
+pub mod value;
+pub use value::{Value, WrongType};
+
+pub mod db;
+pub use db::Db;
+
+pub mod cmd;
+pub use cmd::Command;
 
 /// Default port that a redis server listens on.
 ///
@@ -0,0 +1,370 @@
+use crate::frame::{self, Frame, Invalid, Limit, ProtocolError, ProtocolLimits};
+use bytes::{Buf, BytesMut};
+
+/// An incremental parser for the Redis protocol.
+///
+/// `Frame::check` and `Frame::parse` need the whole message to be buffered
+/// and start over from its first byte every time more data is received. A
+/// large or pipelined message is therefore scanned again on every read. The
+/// `Parser` instead consumes each element from the buffer as soon as it is
+/// complete and keeps the aggregates it is still filling between calls, so
+/// every byte is only looked at once.
+///
+/// The parser does no I/O. It is given a `BytesMut` that the caller fills
+/// from any source, which lets the async `Connection` and `RespCodec` share it
+/// with synchronous callers reading from a blocking socket or a file.
+///
+/// Once `parse` returns an error, the stream cannot be resynchronized and the
+/// parser must not be used anymore.
+#[derive(Debug, Default)]
+pub struct Parser {
+    limits: ProtocolLimits,
+
+    /// Aggregates whose elements are still being received, innermost last.
+    stack: Vec<Partial>,
+
+    /// A bulk string whose header has been consumed while its payload has not
+    /// been fully received yet.
+    blob: Option<Blob>,
+
+    /// Number of bytes at the start of the buffer already known not to
+    /// contain the end of the current line.
+    scanned: usize,
+
+    /// Number of bytes consumed since the parser was created. Errors report
+    /// their position relative to it.
+    offset: u64,
+}
+
+/// An aggregate frame whose elements are still being received.
+#[derive(Debug)]
+struct Partial {
+    /// Type byte of the aggregate.
+    prefix: u8,
+
+    /// Number of frames the aggregate holds. For maps and attributes, this is
+    /// twice the number of entries.
+    len: usize,
+
+    /// Position of the aggregate's first byte.
+    offset: u64,
+
+    frames: Vec<Frame>,
+}
+
+/// A bulk string, verbatim string or blob error whose payload is pending.
+#[derive(Debug, Clone, Copy)]
+struct Blob {
+    /// Type byte of the frame.
+    prefix: u8,
+
+    /// Length of the payload.
+    len: usize,
+
+    /// Position of the frame's first byte.
+    offset: u64,
+}
+
+/// Outcome of reading the next element from the buffer.
+enum Step {
+    /// A complete frame was read.
+    Frame(Frame),
+
+    /// A header was read, the parser expects the data it announces next.
+    Header,
+
+    /// More data is needed.
+    Incomplete,
+}
+
+impl Parser {
+    /// Returns a parser enforcing `limits` on the frames it reads.
+    pub fn new(limits: ProtocolLimits) -> Parser {
+        Parser {
+            limits,
+            ..Parser::default()
+        }
+    }
+
+    /// Returns the limits enforced on parsed frames.
+    pub fn limits(&self) -> &ProtocolLimits {
+        &self.limits
+    }
+
+    /// Sets the limits enforced on parsed frames.
+    pub fn set_limits(&mut self, limits: ProtocolLimits) {
+        self.limits = limits;
+    }
+
+    /// Returns `true` if part of a frame has been consumed but the frame is
+    /// not complete yet. Hitting the end of the stream in that state means the
+    /// peer went away in the middle of sending a frame.
+    pub fn has_partial_frame(&self) -> bool {
+        !self.stack.is_empty() || self.blob.is_some()
+    }
+
+    /// Parses the next frame out of `buf`.
+    ///
+    /// Returns `None` if `buf` does not hold a complete frame yet. The bytes
+    /// parsed so far are consumed from `buf` anyway, and parsing resumes where
+    /// it stopped once more data has been appended to `buf`. Bulk strings are
+    /// split off `buf` without copying.
+    ///
+    /// Inline commands are returned as an array of bulk strings, like
+    /// `Frame::parse` does.
+    ///
+    /// Errors are located relative to the first byte the parser was given.
+    pub fn parse(&mut self, buf: &mut BytesMut) -> Result<Option<Frame>, ProtocolError> {
+        loop {
+            let mut frame = match self.next(buf)? {
+                Step::Frame(frame) => frame,
+                Step::Header => continue,
+                Step::Incomplete => return Ok(None),
+            };
+
+            // Add the frame to the aggregate it belongs to. This may complete
+            // the aggregate, which is then added to its own parent in turn.
+            loop {
+                let partial = match self.stack.last_mut() {
+                    Some(partial) => partial,
+                    None => return Ok(Some(frame)),
+                };
+
+                partial.frames.push(frame);
+
+                if partial.frames.len() < partial.len {
+                    break;
+                }
+
+                frame = self.stack.pop().unwrap().finish();
+            }
+        }
+    }
+
+    /// Returns the error reported when the stream ends while `buf` still
+    /// holds `remaining` unparsed bytes, or `None` if the stream ended between
+    /// two frames.
+    pub fn eof(&self, remaining: usize) -> Option<ProtocolError> {
+        if remaining == 0 && !self.has_partial_frame() {
+            return None;
+        }
+
+        // Point at the innermost frame that was cut short.
+        let offset = match (self.blob, self.stack.last()) {
+            (Some(blob), _) => blob.offset,
+            (None, Some(partial)) if remaining == 0 => partial.offset,
+            _ => self.offset,
+        };
+
+        Some(ProtocolError::UnexpectedEof {
+            offset,
+            path: self.path(),
+        })
+    }
+
+    /// Reads the next element from `buf`.
+    fn next(&mut self, buf: &mut BytesMut) -> Result<Step, ProtocolError> {
+        if let Some(Blob {
+            prefix,
+            len,
+            offset,
+        }) = self.blob
+        {
+            // The data is followed by "\r\n". With limits high enough, the
+            // length may be too close to `usize::MAX` to account for it.
+            let blob_len = len.checked_add(2).ok_or_else(|| self.bad_length(offset))?;
+
+            if buf.len() < blob_len {
+                return Ok(Step::Incomplete);
+            }
+
+            let data = buf.split_to(len).freeze();
+
+            if &buf[..2] != b"\r\n" {
+                return Err(Invalid::Format.at(offset, self.path()));
+            }
+
+            buf.advance(2);
+            self.blob = None;
+            self.offset += len as u64 + 2;
+
+            return frame::blob_frame(prefix, data)
+                .map(Step::Frame)
+                .map_err(|invalid| invalid.at(offset, self.path()));
+        }
+
+        if buf.is_empty() {
+            return Ok(Step::Incomplete);
+        }
+
+        let offset = self.offset;
+
+        // Inline commands can only start a message, never be nested.
+        if self.stack.is_empty() && !frame::is_type_byte(buf[0]) {
+            let end = match self.find(buf, b"\n")? {
+                Some(end) => end,
+                None => return Ok(Step::Incomplete),
+            };
+
+            let line = buf.split_to(end + 1);
+            self.offset += line.len() as u64;
+
+            let line = &line[..end];
+            let args = frame::split_args(line.strip_suffix(b"\r").unwrap_or(line))
+                .map_err(|invalid| invalid.at(offset, vec![]))?;
+
+            return Ok(Step::Frame(Frame::Array(
+                args.into_iter().map(Frame::Bulk).collect(),
+            )));
+        }
+
+        let end = match self.find(buf, b"\r\n")? {
+            Some(end) => end,
+            None => return Ok(Step::Incomplete),
+        };
+
+        let line = buf.split_to(end + 2);
+        self.offset += line.len() as u64;
+
+        let prefix = line[0];
+        let line = &line[1..end];
+
+        match prefix {
+            b'$' | b'=' | b'!' => {
+                let len = match frame::length(line) {
+                    Ok(Some(len)) => len,
+                    // A null bulk string "$-1\r\n"
+                    Ok(None) if prefix == b'$' => return Ok(Step::Frame(Frame::Null)),
+                    _ => return Err(self.bad_length(offset)),
+                };
+
+                if len > self.limits.max_bulk_len {
+                    return Err(self.limit(Limit::BulkLen, offset));
+                }
+
+                self.blob = Some(Blob {
+                    prefix,
+                    len,
+                    offset,
+                });
+
+                Ok(Step::Header)
+            }
+            b'*' | b'~' | b'>' | b'%' | b'|' => {
+                let len = match frame::length(line) {
+                    Ok(Some(len)) => len,
+                    // A RESP2 null array "*-1\r\n"
+                    Ok(None) if prefix == b'*' => return Ok(Step::Frame(Frame::Null)),
+                    _ => return Err(self.bad_length(offset)),
+                };
+
+                if len > self.limits.max_multibulk_len {
+                    return Err(self.limit(Limit::MultibulkLen, offset));
+                }
+
+                if self.stack.len() >= self.limits.max_depth {
+                    return Err(self.limit(Limit::Depth, offset));
+                }
+
+                let partial = Partial {
+                    prefix,
+                    len: if matches!(prefix, b'%' | b'|') {
+                        len * 2
+                    } else {
+                        len
+                    },
+                    offset,
+                    // Do not trust the announced length for the allocation,
+                    // the elements may never be sent.
+                    frames: Vec::with_capacity(len.min(1024)),
+                };
+
+                if partial.len == 0 {
+                    return Ok(Step::Frame(partial.finish()));
+                }
+
+                self.stack.push(partial);
+
+                Ok(Step::Header)
+            }
+            b'+' | b'-' | b':' | b'_' | b'#' | b',' | b'(' => frame::line_frame(prefix, line)
+                .map(Step::Frame)
+                .map_err(|invalid| invalid.at(offset, self.path())),
+            byte => Err(ProtocolError::InvalidTypeByte {
+                byte,
+                offset,
+                path: self.path(),
+            }),
+        }
+    }
+
+    /// Returns the position of `delim` ending the line at the start of `buf`.
+    ///
+    /// Bytes that were already scanned are not scanned again on the next call.
+    /// The line may not exceed the limit on inline commands, even when it has
+    /// not been fully received yet.
+    fn find(&mut self, buf: &[u8], delim: &[u8]) -> Result<Option<usize>, ProtocolError> {
+        let start = self.scanned.min(buf.len());
+
+        let found = buf[start..]
+            .windows(delim.len())
+            .position(|window| window == delim)
+            .map(|i| start + i);
+
+        match found {
+            Some(end) if end <= self.limits.max_inline_len => {
+                self.scanned = 0;
+                Ok(Some(end))
+            }
+            None if buf.len() <= self.limits.max_inline_len => {
+                // The last bytes may be the start of a delimiter that is only
+                // partially received.
+                self.scanned = (buf.len() + 1).saturating_sub(delim.len());
+                Ok(None)
+            }
+            _ => Err(self.limit(Limit::InlineLen, self.offset)),
+        }
+    }
+
+    /// Returns the position of the element currently being parsed within the
+    /// aggregates that are still being received.
+    fn path(&self) -> Vec<usize> {
+        self.stack
+            .iter()
+            .map(|partial| partial.frames.len())
+            .collect()
+    }
+
+    fn bad_length(&self, offset: u64) -> ProtocolError {
+        ProtocolError::BadLength {
+            offset,
+            path: self.path(),
+        }
+    }
+
+    fn limit(&self, limit: Limit, offset: u64) -> ProtocolError {
+        ProtocolError::LimitExceeded {
+            limit,
+            offset,
+            path: self.path(),
+        }
+    }
+}
+
+impl Partial {
+    /// Converts the received elements into the aggregate frame.
+    fn finish(self) -> Frame {
+        if !matches!(self.prefix, b'%' | b'|') {
+            return frame::aggregate_frame(self.prefix, self.frames);
+        }
+
+        let mut frames = self.frames.into_iter();
+        let mut pairs = Vec::with_capacity(self.len / 2);
+
+        while let (Some(key), Some(value)) = (frames.next(), frames.next()) {
+            pairs.push((key, value));
+        }
+
+        frame::pairs_frame(self.prefix, pairs)
+    }
+}
@@ -0,0 +1,666 @@
+//! Serde support for frames, enabled with the `serde` feature.
+//!
+//! `to_frame` serializes a value into a frame and `from_frame` deserializes a
+//! frame into a value, which lets replies be decoded straight into domain
+//! types:
+//!
+//! ```
+//! # use mini_redis_rs::serde_frame::from_frame;
+//! # use mini_redis_rs::Frame;
+//! #[derive(serde::Deserialize)]
+//! struct User {
+//!     name: String,
+//!     visits: u64,
+//! }
+//!
+//! // The reply to `HGETALL user:1` over RESP2
+//! let reply = Frame::Array(vec![
+//!     Frame::Bulk("name".into()),
+//!     Frame::Bulk("ferris".into()),
+//!     Frame::Bulk("visits".into()),
+//!     Frame::Bulk("12".into()),
+//! ]);
+//!
+//! let user: User = from_frame(reply).unwrap();
+//! assert_eq!(("ferris", 12), (user.name.as_str(), user.visits));
+//! ```
+//!
+//! Structs and maps are serialized as maps, keyed by field name for structs.
+//! Sequences, tuples and tuple structs are serialized as arrays. Enum
+//! variants are serialized like `serde_json` does: unit variants as their
+//! name, and other variants as a map with a single entry from the name to the
+//! content.
+//!
+//! Deserializing accepts the same frames as the `FromFrame` conversions, so
+//! structs can be read from the flat arrays maps are downgraded to in RESP2,
+//! and numbers from bulk strings.
+
+use crate::convert::{self, Error, FromFrame};
+use crate::frame::Frame;
+use bytes::Bytes;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use std::fmt;
+
+/// Serializes `value` into a frame.
+pub fn to_frame<T: Serialize + ?Sized>(value: &T) -> Result<Frame, Error> {
+    value.serialize(Serializer)
+}
+
+/// Deserializes a value out of `frame`.
+pub fn from_frame<T: DeserializeOwned>(frame: Frame) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(frame))
+}
+
+/// A serde `Serializer` producing frames.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Serializer;
+
+/// A serde `Deserializer` reading a frame.
+#[derive(Debug)]
+pub struct Deserializer {
+    frame: Frame,
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Custom(msg.to_string())
+    }
+}
+
+/// Wraps the content of an enum variant in a map from the variant's name.
+fn variant(name: &'static str, content: Frame) -> Frame {
+    Frame::Map(vec![(
+        Frame::Bulk(Bytes::from_static(name.as_bytes())),
+        content,
+    )])
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Frame;
+    type Error = Error;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeMap;
+
+    fn serialize_bool(self, v: bool) -> Result<Frame, Error> {
+        Ok(Frame::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Frame, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Frame, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Frame, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Frame, Error> {
+        Ok(Frame::Integer(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Frame, Error> {
+        Ok(convert::ToFrame::to_frame(&v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Frame, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Frame, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Frame, Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Frame, Error> {
+        Ok(convert::ToFrame::to_frame(&v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Frame, Error> {
+        Ok(convert::ToFrame::to_frame(&v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Frame, Error> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Frame, Error> {
+        Ok(Frame::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Frame, Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Frame, Error> {
+        self.serialize_bytes(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Frame, Error> {
+        Ok(Frame::Bulk(Bytes::copy_from_slice(v)))
+    }
+
+    fn serialize_none(self) -> Result<Frame, Error> {
+        Ok(Frame::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Frame, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Frame, Error> {
+        Ok(Frame::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Frame, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Frame, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Frame, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Frame, Error> {
+        Ok(variant(name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            frames: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            frames: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap {
+            pairs: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap {
+            pairs: Vec::with_capacity(len),
+            key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+/// Serializes sequences, tuples and tuple variants into an array.
+#[derive(Debug)]
+pub struct SerializeArray {
+    frames: Vec<Frame>,
+    variant: Option<&'static str>,
+}
+
+impl SerializeArray {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.frames.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Frame, Error> {
+        let frame = Frame::Array(self.frames);
+
+        Ok(match self.variant {
+            Some(name) => variant(name, frame),
+            None => frame,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Frame;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Frame, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Frame;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Frame, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Frame;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Frame, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = Frame;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Frame, Error> {
+        self.finish()
+    }
+}
+
+/// Serializes maps, structs and struct variants into a map.
+#[derive(Debug)]
+pub struct SerializeMap {
+    pairs: Vec<(Frame, Frame)>,
+
+    /// Key whose value has not been serialized yet.
+    key: Option<Frame>,
+
+    variant: Option<&'static str>,
+}
+
+impl SerializeMap {
+    fn finish(self) -> Result<Frame, Error> {
+        let frame = Frame::Map(self.pairs);
+
+        Ok(match self.variant {
+            Some(name) => variant(name, frame),
+            None => frame,
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = Frame;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <Error as ser::Error>::custom("map value without a key"))?;
+
+        self.pairs.push((key, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Frame, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = Frame;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.pairs
+            .push((key.serialize(Serializer)?, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Frame, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeMap {
+    type Ok = Frame;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Frame, Error> {
+        self.finish()
+    }
+}
+
+impl Deserializer {
+    /// Returns a deserializer reading `frame`.
+    pub fn new(frame: Frame) -> Deserializer {
+        Deserializer { frame }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Deserializer {
+    type Deserializer = Deserializer;
+
+    fn into_deserializer(self) -> Deserializer {
+        self
+    }
+}
+
+macro_rules! deserialize_from_frame {
+    ($($method:ident $visit:ident $ty:ty;)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            visitor.$visit(<$ty>::from_frame(self.frame)?)
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.frame {
+            Frame::Simple(text) | Frame::BigNumber(text) => visitor.visit_string(text),
+            Frame::Integer(val) => visitor.visit_i64(val),
+            Frame::Bulk(data) | Frame::Verbatim(_, data) => match std::str::from_utf8(&data) {
+                Ok(text) => visitor.visit_str(text),
+                Err(_) => visitor.visit_bytes(&data),
+            },
+            Frame::Null | Frame::NullArray => visitor.visit_none(),
+            Frame::Double(val) => visitor.visit_f64(val),
+            Frame::Boolean(val) => visitor.visit_bool(val),
+            Frame::Array(_) | Frame::Set(_) | Frame::Push(_) => self.deserialize_seq(visitor),
+            Frame::Map(_) | Frame::Attribute(_) => self.deserialize_map(visitor),
+            frame => Err(convert::unexpected("value", &frame)),
+        }
+    }
+
+    deserialize_from_frame! {
+        deserialize_bool visit_bool bool;
+        deserialize_i8 visit_i8 i8;
+        deserialize_i16 visit_i16 i16;
+        deserialize_i32 visit_i32 i32;
+        deserialize_i64 visit_i64 i64;
+        deserialize_i128 visit_i128 i128;
+        deserialize_u8 visit_u8 u8;
+        deserialize_u16 visit_u16 u16;
+        deserialize_u32 visit_u32 u32;
+        deserialize_u64 visit_u64 u64;
+        deserialize_u128 visit_u128 u128;
+        deserialize_f32 visit_f32 f32;
+        deserialize_f64 visit_f64 f64;
+        deserialize_string visit_string String;
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let text = String::from_frame(self.frame)?;
+        let mut chars = text.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::InvalidValue {
+                expected: "char",
+                value: text,
+            }),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_bytes(&Bytes::from_frame(self.frame)?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.frame {
+            Frame::Null | Frame::NullArray => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    /// Any frame but an error reply deserializes into a unit, so `()` can be
+    /// used for replies such as `+OK`.
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.frame {
+            Frame::Error(msg) => Err(Error::Reply(msg)),
+            _ => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let frames = convert::elements(self.frame)?;
+        let mut seq = de::value::SeqDeserializer::new(frames.into_iter().map(Deserializer::new));
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let pairs = convert::pairs(self.frame)?;
+        let mut map = de::value::MapDeserializer::new(
+            pairs
+                .into_iter()
+                .map(|(key, value)| (Deserializer::new(key), Deserializer::new(value))),
+        );
+        let value = visitor.visit_map(&mut map)?;
+        map.end()?;
+
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let (name, content) = match self.frame {
+            Frame::Map(mut pairs) if pairs.len() == 1 => {
+                let (name, content) = pairs.pop().unwrap();
+                (name, Some(content))
+            }
+            frame => (frame, None),
+        };
+
+        visitor.visit_enum(Enum { name, content })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// An enum variant, read from its name and optional content.
+struct Enum {
+    name: Frame,
+    content: Option<Frame>,
+}
+
+impl<'de> de::EnumAccess<'de> for Enum {
+    type Error = Error;
+    type Variant = Variant;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Variant), Error> {
+        let name = seed.deserialize(Deserializer::new(self.name))?;
+        Ok((name, Variant(self.content)))
+    }
+}
+
+/// The content of an enum variant.
+struct Variant(Option<Frame>);
+
+impl Variant {
+    fn content(self) -> Result<Deserializer, Error> {
+        self.0
+            .map(Deserializer::new)
+            .ok_or_else(|| <Error as de::Error>::custom("missing enum variant content"))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Variant {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.0 {
+            Some(Frame::Null | Frame::NullArray) | None => Ok(()),
+            Some(_) => Err(de::Error::custom("unexpected content for unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self.content()?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_tuple(self.content()?, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_struct(self.content()?, "", fields, visitor)
+    }
+}
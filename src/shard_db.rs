@@ -16,10 +16,16 @@ pub fn hash(s: String) -> usize {
     hash
 }
 
+/// A map split into independently locked shards.
+///
+/// The server does not use it anymore: its keyspace is `Db`, which holds
+/// `Value`s in shards picked with `hash`, along with their expiration.
+#[deprecated(note = "use `Db`, the sharded keyspace holding `Value`s")]
 pub struct ShardDb<K: ToString, V> {
     shards: Vec<Mutex<HashMap<K, V>>>,
 }
 
+#[allow(deprecated)]
 impl<K: ToString, V> ShardDb<K, V> {
     pub fn new(size: usize) -> ShardDb<K, V> {
         assert!(size > 0, "`size` must be greater than 0");
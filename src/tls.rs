@@ -0,0 +1,112 @@
+//! TLS support, built on rustls.
+//!
+//! The server side wraps accepted sockets with a `TlsAcceptor` built from
+//! `server_config`, the client side connects with `connect`. Either way, the
+//! resulting TLS stream is used by a `Connection` like a plain socket.
+//!
+//! Certificates and keys are read from PEM files, as configured in Redis with
+//! `tls-cert-file`, `tls-key-file` and `tls-ca-cert-file`.
+
+use crate::connection::Connection;
+use crate::Result;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio_rustls::TlsConnector;
+
+pub use tokio_rustls::TlsAcceptor;
+
+/// Returns the configuration of a TLS server presenting the certificate chain
+/// in `cert_file`, signed with the private key in `key_file`.
+///
+/// When `ca_file` is given, clients must authenticate with a certificate
+/// signed by one of the certificate authorities it holds.
+pub fn server_config(
+    cert_file: &Path,
+    key_file: &Path,
+    ca_file: Option<&Path>,
+) -> Result<ServerConfig> {
+    let builder =
+        ServerConfig::builder_with_provider(provider()).with_safe_default_protocol_versions()?;
+
+    let builder = match ca_file {
+        Some(ca_file) => builder.with_client_cert_verifier(
+            WebPkiClientVerifier::builder_with_provider(Arc::new(load_roots(ca_file)?), provider())
+                .build()?,
+        ),
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(builder.with_single_cert(load_certs(cert_file)?, load_key(key_file)?)?)
+}
+
+/// Returns the configuration of a TLS client trusting the certificate
+/// authorities in `ca_file`.
+///
+/// `identity` holds the certificate chain and private key files the client
+/// authenticates with, for servers requiring it.
+pub fn client_config(ca_file: &Path, identity: Option<(&Path, &Path)>) -> Result<ClientConfig> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(load_roots(ca_file)?);
+
+    Ok(match identity {
+        Some((cert_file, key_file)) => {
+            builder.with_client_auth_cert(load_certs(cert_file)?, load_key(key_file)?)?
+        }
+        None => builder.with_no_client_auth(),
+    })
+}
+
+/// Opens a TLS connection to the server at `addr`, whose certificate must be
+/// valid for `domain`.
+pub async fn connect<T: ToSocketAddrs>(
+    addr: T,
+    domain: &str,
+    config: Arc<ClientConfig>,
+) -> Result<Connection<TlsStream<TcpStream>>> {
+    let domain = ServerName::try_from(domain.to_string())?;
+
+    let socket = TcpStream::connect(addr).await?;
+    let stream = TlsConnector::from(config).connect(domain, socket).await?;
+
+    Ok(Connection::new(stream))
+}
+
+/// Reads the certificates in the PEM file at `path`.
+pub fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)?.collect::<std::result::Result<Vec<_>, _>>()?;
+
+    if certs.is_empty() {
+        return Err(format!("no certificate found in {}", path.display()).into());
+    }
+
+    Ok(certs)
+}
+
+/// Reads the first private key in the PEM file at `path`.
+pub fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    Ok(PrivateKeyDer::from_pem_file(path)?)
+}
+
+fn load_roots(path: &Path) -> Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+
+    for cert in load_certs(path)? {
+        roots.add(cert)?;
+    }
+
+    Ok(roots)
+}
+
+/// Returns the cryptography used for TLS. Passing it explicitly avoids
+/// depending on a process-wide default being installed.
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
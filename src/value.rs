@@ -0,0 +1,160 @@
+//! Values stored in the keyspace.
+//!
+//! Like Redis, a key holds one of several kinds of values, and commands only
+//! operate on the kind they are meant for. Using a command on a key holding
+//! another kind of value fails with `WrongType`, which is reported to the
+//! client as a `WRONGTYPE` error.
+
+use crate::Frame;
+use bytes::Bytes;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// Value of a key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(Bytes),
+    List(VecDeque<Bytes>),
+    Hash(HashMap<Bytes, Bytes>),
+    Set(HashSet<Bytes>),
+    SortedSet(SortedSet),
+}
+
+/// Set of members ordered by score, then by member for equal scores.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SortedSet {
+    /// Score of each member.
+    scores: HashMap<Bytes, f64>,
+
+    /// Members ordered by score, which is repeated here so that ranges of
+    /// scores or ranks can be walked in order.
+    ordered: BTreeSet<(Score, Bytes)>,
+}
+
+/// A score, totally ordered so that it can be used in a `BTreeSet`.
+#[derive(Debug, Clone, Copy)]
+struct Score(f64);
+
+/// Error returned when a key holds another kind of value than the one an
+/// operation expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrongType;
+
+impl Value {
+    /// Returns the name of the kind of value, as reported by `TYPE`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+        }
+    }
+
+    /// Returns the string held by the value.
+    pub fn as_string(&self) -> Result<&Bytes, WrongType> {
+        match self {
+            Value::String(data) => Ok(data),
+            _ => Err(WrongType),
+        }
+    }
+}
+
+impl From<Bytes> for Value {
+    fn from(data: Bytes) -> Value {
+        Value::String(data)
+    }
+}
+
+impl SortedSet {
+    pub fn new() -> SortedSet {
+        SortedSet::default()
+    }
+
+    /// Returns the number of members.
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    /// Returns `true` if there are no members.
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// Returns the score of `member`, if it is in the set.
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Adds `member` with `score`, or updates its score if it is already in
+    /// the set.
+    ///
+    /// Returns `true` if the member was added.
+    pub fn insert(&mut self, member: Bytes, score: f64) -> bool {
+        let previous = self.scores.insert(member.clone(), score);
+
+        if let Some(previous) = previous {
+            self.ordered.remove(&(Score(previous), member.clone()));
+        }
+
+        self.ordered.insert((Score(score), member));
+        previous.is_none()
+    }
+
+    /// Removes `member` from the set.
+    ///
+    /// Returns `true` if it was in the set.
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self.scores.remove_entry(member) {
+            Some((member, score)) => {
+                self.ordered.remove(&(Score(score), member));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns an iterator over the members and their scores, from the lowest
+    /// score to the highest.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&Bytes, f64)> + '_ {
+        self.ordered.iter().map(|(score, member)| (member, score.0))
+    }
+}
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Score) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Score) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Score) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl WrongType {
+    /// Returns the error reply sent to the client, in the same format as
+    /// Redis.
+    pub fn to_frame(&self) -> Frame {
+        Frame::Error(self.to_string())
+    }
+}
+
+impl std::error::Error for WrongType {}
+
+impl fmt::Display for WrongType {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "WRONGTYPE Operation against a key holding the wrong kind of value".fmt(fmt)
+    }
+}
@@ -0,0 +1,288 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use common::{bulk, command, Fixture};
+use mini_redis_rs::db::{BlockingPop, End, Waiter};
+use mini_redis_rs::{Command, Db, Frame, Value, WrongType};
+use tokio::time::{self, Instant};
+
+fn keys(keys: &[&str]) -> Vec<String> {
+    keys.iter().map(|key| key.to_string()).collect()
+}
+
+fn blocked(db: &Db, keys: &[String], end: End) -> Waiter {
+    match db.pop_or_block(keys, end).unwrap() {
+        BlockingPop::Blocked(waiter) => waiter,
+        BlockingPop::Popped(key, _) => panic!("unexpected value in {}", key),
+    }
+}
+
+#[tokio::test]
+async fn waiters_are_served_in_order() {
+    let db = Db::new();
+
+    let mut first = blocked(&db, &keys(&["a", "b"]), End::Left);
+    let mut second = blocked(&db, &keys(&["b"]), End::Left);
+    let third = blocked(&db, &keys(&["b"]), End::Left);
+
+    // Values are handed to the waiters rather than stored.
+    assert_eq!(
+        Ok(2),
+        db.push(
+            "b".to_string(),
+            End::Right,
+            vec![Bytes::from("1"), Bytes::from("2")]
+        )
+    );
+    assert_eq!(Ok(0), db.llen("b"));
+
+    assert_eq!(Ok(("b".to_string(), Bytes::from("1"))), first.recv().await);
+    assert_eq!(Ok(("b".to_string(), Bytes::from("2"))), second.recv().await);
+
+    // A waiter giving up is removed from the queues.
+    drop(third);
+    drop(first);
+    db.push("b".to_string(), End::Right, vec![Bytes::from("3")])
+        .unwrap();
+    db.push("a".to_string(), End::Right, vec![Bytes::from("4")])
+        .unwrap();
+    assert_eq!(Ok(1), db.llen("a"));
+    assert_eq!(Ok(1), db.llen("b"));
+
+    // A value handed to a waiter that gives up before receiving it is pushed
+    // back where it was popped from.
+    let waiter = blocked(&db, &keys(&["c"]), End::Right);
+    db.push("c".to_string(), End::Right, vec![Bytes::from("x")])
+        .unwrap();
+    assert_eq!(Ok(0), db.llen("c"));
+    drop(waiter);
+    assert_eq!(Ok(vec![Bytes::from("x")]), db.lrange("c", 0, -1));
+
+    match db
+        .pop_or_block(&keys(&["missing", "c"]), End::Left)
+        .unwrap()
+    {
+        BlockingPop::Popped(key, value) => {
+            assert_eq!(("c".to_string(), Bytes::from("x")), (key, value))
+        }
+        BlockingPop::Blocked(_) => panic!("c has a value"),
+    }
+}
+
+#[tokio::test]
+async fn blocking_commands() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    let blocked = tokio::spawn({
+        let db = db.clone();
+
+        async move {
+            for args in [
+                &["BLPOP", "a", "b", "0"][..],
+                &["BLMOVE", "a", "c", "RIGHT", "LEFT", "0"],
+                &["BRPOP", "a", "0.05"],
+            ] {
+                let cmd = Command::from_frame(command(args)).unwrap();
+                cmd.apply(&db, &mut server, &mut shutdown).await.unwrap();
+                server.flush().await.unwrap();
+            }
+        }
+    });
+
+    time::sleep(Duration::from_millis(20)).await;
+    db.push("b".to_string(), End::Left, vec![Bytes::from("1")])
+        .unwrap();
+    assert_eq!(
+        Some(Frame::Array(vec![bulk("b"), bulk("1")])),
+        client.read_frame().await.unwrap()
+    );
+
+    time::sleep(Duration::from_millis(20)).await;
+    db.push("a".to_string(), End::Left, vec![Bytes::from("2")])
+        .unwrap();
+    assert_eq!(Some(bulk("2")), client.read_frame().await.unwrap());
+    assert_eq!(Ok(vec![Bytes::from("2")]), db.lrange("c", 0, -1));
+
+    let start = Instant::now();
+    assert_eq!(Some(Frame::Null), client.read_frame().await.unwrap());
+    assert!(start.elapsed() >= Duration::from_millis(40));
+
+    blocked.await.unwrap();
+}
+
+#[tokio::test]
+async fn disconnected_clients_stop_blocking() {
+    let Fixture {
+        client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    let blocked = tokio::spawn({
+        let db = db.clone();
+        let cmd = Command::from_frame(command(&["BLPOP", "a", "0"])).unwrap();
+
+        async move { cmd.apply(&db, &mut server, &mut shutdown).await }
+    });
+
+    time::sleep(Duration::from_millis(20)).await;
+    drop(client);
+    blocked.await.unwrap().unwrap();
+
+    // The value is not handed to the client that went away.
+    db.push("a".to_string(), End::Left, vec![Bytes::from("1")])
+        .unwrap();
+    assert_eq!(Ok(1), db.llen("a"));
+}
+
+#[tokio::test]
+async fn blocked_moves() {
+    let db = Db::new();
+
+    let blocked_move = |source: &str, destination: &str| match db
+        .lmove_or_block(source, destination, End::Left, End::Right)
+        .unwrap()
+    {
+        BlockingPop::Blocked(waiter) => waiter,
+        BlockingPop::Popped(key, _) => panic!("unexpected value in {}", key),
+    };
+
+    // The value is in the destination by the time the waiter is served, and
+    // serves the clients blocked on it in turn.
+    let mut first = blocked_move("a", "b");
+    let mut second = blocked(&db, &keys(&["b"]), End::Left);
+    db.push("a".to_string(), End::Right, vec![Bytes::from("1")])
+        .unwrap();
+
+    assert_eq!(Ok(("a".to_string(), Bytes::from("1"))), first.recv().await);
+    assert_eq!(Ok(("b".to_string(), Bytes::from("1"))), second.recv().await);
+
+    // A value moved for a waiter that gives up stays moved.
+    let waiter = blocked_move("a", "c");
+    db.push("a".to_string(), End::Right, vec![Bytes::from("2")])
+        .unwrap();
+    drop(waiter);
+    assert_eq!(Ok(0), db.llen("a"));
+    assert_eq!(Ok(vec![Bytes::from("2")]), db.lrange("c", 0, -1));
+
+    // When the destination holds another kind of value by the time the waiter
+    // is served, nothing is moved.
+    let mut waiter = blocked_move("a", "d");
+    db.set_value("d".to_string(), Value::String(Bytes::from("x")));
+    db.push("a".to_string(), End::Right, vec![Bytes::from("3")])
+        .unwrap();
+
+    assert_eq!(Err(WrongType), waiter.recv().await);
+    assert_eq!(Ok(vec![Bytes::from("3")]), db.lrange("a", 0, -1));
+
+    // Or right away, even though the source is empty.
+    assert!(matches!(
+        db.lmove_or_block("missing", "d", End::Left, End::Right),
+        Err(WrongType)
+    ));
+}
+
+#[tokio::test]
+async fn timeouts_reply_null_arrays() {
+    use tokio::io::AsyncReadExt;
+
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    for args in [
+        &["BLPOP", "a", "0.01"][..],
+        &["BLMOVE", "a", "b", "LEFT", "LEFT", "0.01"],
+    ] {
+        let cmd = Command::from_frame(command(args)).unwrap();
+        cmd.apply(&db, &mut server, &mut shutdown).await.unwrap();
+    }
+    server.flush().await.unwrap();
+
+    let mut reply = [0; 10];
+    client.get_mut().read_exact(&mut reply).await.unwrap();
+    assert_eq!(b"*-1\r\n*-1\r\n", &reply);
+}
+
+#[tokio::test(start_paused = true)]
+async fn served_as_the_timeout_expires() {
+    // Whichever of the push and the timeout is handled first, the reply tells
+    // whether the value was moved.
+    for push_first in [true, false] {
+        let Fixture {
+            mut client,
+            mut server,
+            db,
+            notify: _notify,
+            mut shutdown,
+        } = Fixture::new();
+
+        let push = |db: Arc<Db>| async move {
+            time::sleep(Duration::from_millis(100)).await;
+            db.push("a".to_string(), End::Left, vec![Bytes::from("1")])
+                .unwrap();
+        };
+        let pusher = push_first.then(|| tokio::spawn(push(db.clone())));
+
+        let blocked = tokio::spawn({
+            let db = db.clone();
+            let cmd =
+                Command::from_frame(command(&["BLMOVE", "a", "b", "LEFT", "LEFT", "0.1"])).unwrap();
+
+            async move {
+                cmd.apply(&db, &mut server, &mut shutdown).await.unwrap();
+                server.flush().await.unwrap();
+            }
+        });
+        let pusher = pusher.unwrap_or_else(|| tokio::spawn(push(db.clone())));
+
+        let reply = client.read_frame().await.unwrap();
+        blocked.await.unwrap();
+        pusher.await.unwrap();
+
+        if reply == Some(bulk("1")) {
+            assert_eq!(Ok(vec![Bytes::from("1")]), db.lrange("b", 0, -1));
+            assert_eq!(Ok(0), db.llen("a"));
+        } else {
+            assert_eq!(Some(Frame::Null), reply);
+            assert_eq!(Ok(vec![Bytes::from("1")]), db.lrange("a", 0, -1));
+            assert_eq!(Ok(0), db.llen("b"));
+        }
+    }
+}
+
+#[test]
+fn parse_timeouts() {
+    use mini_redis_rs::cmd::ParseError;
+
+    let err = |args| Command::from_frame(command(args)).unwrap_err();
+
+    assert!(Command::from_frame(command(&["BLPOP", "a", "0.5"])).is_ok());
+    assert_eq!(
+        ParseError::Other("timeout is negative".to_string()),
+        err(&["BLPOP", "a", "-1"])
+    );
+    assert_eq!(
+        ParseError::Other("timeout is not a float or out of range".to_string()),
+        err(&["BRPOP", "a", "soon"])
+    );
+    assert_eq!(
+        ParseError::WrongArity("blpop".to_string()),
+        err(&["BLPOP", "a"])
+    );
+}
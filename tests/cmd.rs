@@ -0,0 +1,298 @@
+mod common;
+
+use bytes::Bytes;
+use common::{bulk, command, Fixture};
+use mini_redis_rs::cmd::ParseError;
+use mini_redis_rs::{Command, Frame, Protocol};
+
+#[test]
+fn parse_commands() {
+    match Command::from_frame(command(&["SET", "key", "value"])).unwrap() {
+        Command::Set(cmd) => {
+            assert_eq!("key", cmd.key());
+            assert_eq!(&Bytes::from_static(b"value"), cmd.value());
+        }
+        cmd => panic!("unexpected command {:?}", cmd),
+    }
+
+    let cmd = Command::from_frame(command(&["fOo", "bar"])).unwrap();
+    assert_eq!("foo", cmd.get_name());
+}
+
+#[test]
+fn parse_errors() {
+    let err = |args| Command::from_frame(command(args)).unwrap_err();
+
+    assert_eq!(ParseError::WrongArity("get".to_string()), err(&["GET"]));
+    assert_eq!(
+        ParseError::WrongArity("get".to_string()),
+        err(&["GET", "a", "b"])
+    );
+    assert_eq!(ParseError::Syntax, err(&["SET", "a", "b", "c"]));
+    assert_eq!(
+        Frame::Error("ERR wrong number of arguments for 'set' command".to_string()),
+        err(&["SET", "a"]).to_frame()
+    );
+
+    let not_a_command = Frame::Array(vec![Frame::Integer(1)]);
+    assert_eq!(
+        ParseError::Protocol,
+        Command::from_frame(not_a_command).unwrap_err()
+    );
+}
+
+#[tokio::test]
+async fn apply_commands() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    for args in [
+        &["GET", "key"][..],
+        &["SET", "key", "value"],
+        &["GET", "key"],
+        &["TYPE", "key"],
+        &["HELLO", "3"],
+        &["PING"],
+        &["NOPE"],
+    ] {
+        let cmd = Command::from_frame(command(args)).unwrap();
+        cmd.apply(&db, &mut server, &mut shutdown).await.unwrap();
+    }
+    server.flush().await.unwrap();
+
+    assert_eq!(Some(Frame::Null), client.read_frame().await.unwrap());
+    assert_eq!(
+        Some(Frame::Simple("OK".to_string())),
+        client.read_frame().await.unwrap()
+    );
+    assert_eq!(Some(bulk("value")), client.read_frame().await.unwrap());
+    assert_eq!(
+        Some(Frame::Simple("string".to_string())),
+        client.read_frame().await.unwrap()
+    );
+    assert!(matches!(
+        client.read_frame().await.unwrap(),
+        Some(Frame::Map(_))
+    ));
+    assert_eq!(Protocol::Resp3, server.protocol());
+    assert_eq!(
+        Some(Frame::Simple("PONG".to_string())),
+        client.read_frame().await.unwrap()
+    );
+    assert_eq!(
+        Some(Frame::Error(
+            "ERR unknown command 'nope', with args beginning with: ".to_string()
+        )),
+        client.read_frame().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn unknown_command_arguments_are_sanitized() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    let long = "x".repeat(200);
+    for args in [&["NOPE", "a\r\n+OK"][..], &["NOPE", &long, "b"]] {
+        let cmd = Command::from_frame(command(args)).unwrap();
+        cmd.apply(&db, &mut server, &mut shutdown).await.unwrap();
+    }
+    server.flush().await.unwrap();
+
+    // The argument cannot inject a frame of its own.
+    assert_eq!(
+        Some(Frame::Error(
+            "ERR unknown command 'nope', with args beginning with: 'a  +OK' ".to_string()
+        )),
+        client.read_frame().await.unwrap()
+    );
+
+    // Like Redis, at most 128 bytes of arguments are quoted.
+    assert_eq!(
+        Some(Frame::Error(format!(
+            "ERR unknown command 'nope', with args beginning with: '{}' ",
+            &long[..128]
+        ))),
+        client.read_frame().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn hello_negotiates_the_protocol() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    let hello = |args: &[&str]| Command::from_frame(command(args)).unwrap();
+
+    // An unsupported version is rejected and the protocol is kept.
+    hello(&["HELLO", "4"])
+        .apply(&db, &mut server, &mut shutdown)
+        .await
+        .unwrap();
+    hello(&["HELLO"])
+        .apply(&db, &mut server, &mut shutdown)
+        .await
+        .unwrap();
+    server.flush().await.unwrap();
+
+    assert_eq!(
+        Some(Frame::Error(
+            "NOPROTO unsupported protocol version".to_string()
+        )),
+        client.read_frame().await.unwrap()
+    );
+    // In RESP2, the map is flattened into an array.
+    match client.read_frame().await.unwrap() {
+        Some(Frame::Array(fields)) => {
+            assert_eq!(10, fields.len());
+            assert_eq!(Frame::Integer(2), fields[5]);
+        }
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+    assert_eq!(Protocol::Resp2, server.protocol());
+
+    // The reply to `HELLO 3` is already encoded with RESP3.
+    hello(&["HELLO", "3"])
+        .apply(&db, &mut server, &mut shutdown)
+        .await
+        .unwrap();
+    server.flush().await.unwrap();
+
+    match client.read_frame().await.unwrap() {
+        Some(Frame::Map(fields)) => {
+            assert_eq!((bulk("proto"), Frame::Integer(3)), fields[2]);
+        }
+        frame => panic!("unexpected frame {:?}", frame),
+    }
+    assert_eq!(Protocol::Resp3, server.protocol());
+}
+
+#[test]
+fn parse_set_options() {
+    use mini_redis_rs::db::{Condition, Expiry};
+
+    match Command::from_frame(command(&["SET", "k", "v", "xx", "KEEPTTL"])).unwrap() {
+        Command::Set(cmd) => {
+            assert_eq!(Condition::IfPresent, cmd.condition());
+            assert_eq!(Expiry::Keep, cmd.expiry());
+        }
+        cmd => panic!("unexpected command {:?}", cmd),
+    }
+
+    let err = |args| Command::from_frame(command(args)).unwrap_err();
+
+    assert_eq!(ParseError::Syntax, err(&["SET", "k", "v", "NX", "XX"]));
+    assert_eq!(
+        ParseError::Syntax,
+        err(&["SET", "k", "v", "EX", "1", "KEEPTTL"])
+    );
+    assert_eq!(ParseError::Syntax, err(&["SET", "k", "v", "EX"]));
+    assert_eq!(ParseError::NotInteger, err(&["SET", "k", "v", "PX", "x"]));
+    assert_eq!(
+        "invalid expire time in 'set' command",
+        err(&["SET", "k", "v", "EX", "0"]).to_string()
+    );
+    assert_eq!(
+        "invalid expire time in 'expire' command",
+        err(&["EXPIRE", "k", "9223372036854775807"]).to_string()
+    );
+    assert_eq!(
+        ParseError::Syntax,
+        err(&["GETEX", "k", "PERSIST", "EX", "1"])
+    );
+}
+
+#[tokio::test]
+async fn list_commands() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    for (args, expected) in [
+        (&["RPUSH", "list", "a", "b", "c"][..], Frame::Integer(3)),
+        (&["LPOP", "list"], bulk("a")),
+        (
+            &["RPOP", "list", "5"],
+            Frame::Array(vec![bulk("c"), bulk("b")]),
+        ),
+        (&["LPOP", "list"], Frame::Null),
+        (&["LPUSH", "list", "x"], Frame::Integer(1)),
+        (
+            &["LSET", "list", "1", "y"],
+            Frame::Error("ERR index out of range".to_string()),
+        ),
+        (
+            &["LSET", "missing", "0", "y"],
+            Frame::Error("ERR no such key".to_string()),
+        ),
+        (&["LMOVE", "list", "other", "LEFT", "right"], bulk("x")),
+        (&["TYPE", "other"], Frame::Simple("list".to_string())),
+        (
+            &["GET", "other"],
+            Frame::Error(
+                "WRONGTYPE Operation against a key holding the wrong kind of value".to_string(),
+            ),
+        ),
+    ] {
+        let cmd = Command::from_frame(command(args)).unwrap();
+        cmd.apply(&db, &mut server, &mut shutdown).await.unwrap();
+        server.flush().await.unwrap();
+
+        assert_eq!(Some(expected), client.read_frame().await.unwrap());
+    }
+
+    let err = |args| Command::from_frame(command(args)).unwrap_err();
+
+    assert_eq!(
+        ParseError::Other("value is out of range, must be positive".to_string()),
+        err(&["LPOP", "list", "-1"])
+    );
+    assert_eq!(ParseError::Syntax, err(&["LMOVE", "a", "b", "LEFT", "UP"]));
+    assert_eq!(
+        ParseError::WrongArity("rpush".to_string()),
+        err(&["RPUSH", "list"])
+    );
+}
+
+#[tokio::test]
+async fn pop_count_from_a_missing_key() {
+    use tokio::io::AsyncReadExt;
+
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    for args in [&["LPOP", "missing"][..], &["RPOP", "missing", "2"]] {
+        let cmd = Command::from_frame(command(args)).unwrap();
+        cmd.apply(&db, &mut server, &mut shutdown).await.unwrap();
+    }
+    server.flush().await.unwrap();
+
+    // Without a count the reply is a null bulk string, with one a null array.
+    let mut reply = [0; 10];
+    client.get_mut().read_exact(&mut reply).await.unwrap();
+    assert_eq!(b"$-1\r\n*-1\r\n", &reply);
+}
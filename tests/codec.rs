@@ -0,0 +1,26 @@
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use mini_redis_rs::{Frame, Protocol, RespCodec};
+use tokio_util::codec::Framed;
+
+#[tokio::test]
+async fn framed_round_trip() {
+    let (client, server) = tokio::io::duplex(64);
+    let mut client = Framed::new(client, RespCodec::new());
+    let mut server = Framed::new(server, RespCodec::new());
+
+    let cmd = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"GET")),
+        Frame::Bulk(Bytes::from_static(b"hello")),
+    ]);
+    client.send(cmd.clone()).await.unwrap();
+    assert_eq!(cmd, server.next().await.unwrap().unwrap());
+
+    // Switching the server side to RESP3 changes how replies are encoded.
+    server.codec_mut().set_protocol(Protocol::Resp3);
+    server.send(Frame::Boolean(true)).await.unwrap();
+    assert_eq!(Frame::Boolean(true), client.next().await.unwrap().unwrap());
+
+    drop(server);
+    assert!(client.next().await.is_none());
+}
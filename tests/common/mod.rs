@@ -0,0 +1,101 @@
+//! Helpers shared by the integration tests.
+//!
+//! Each test file is its own crate and only uses some of them.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use mini_redis_rs::{Connection, Db, Frame, Shutdown};
+use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa, KeyPair};
+use tokio::io::DuplexStream;
+use tokio::sync::broadcast;
+
+/// Returns the command array for `args`, as sent by a client.
+pub fn command(args: &[&str]) -> Frame {
+    Frame::Array(args.iter().map(|arg| bulk(arg)).collect())
+}
+
+pub fn bulk(value: &str) -> Frame {
+    Frame::Bulk(Bytes::copy_from_slice(value.as_bytes()))
+}
+
+/// A client connected to a server side `Connection` over an in-memory pipe,
+/// with the database and shutdown signal commands are applied with.
+pub struct Fixture {
+    pub client: Connection<DuplexStream>,
+    pub server: Connection<DuplexStream>,
+    pub db: Arc<Db>,
+
+    /// Dropping it notifies `shutdown`.
+    pub notify: broadcast::Sender<()>,
+    pub shutdown: Shutdown,
+}
+
+impl Fixture {
+    pub fn new() -> Fixture {
+        let (client, server) = tokio::io::duplex(4096);
+        let (notify, shutdown) = broadcast::channel(1);
+
+        Fixture {
+            client: Connection::new(client),
+            server: Connection::new(server),
+            db: Arc::new(Db::new()),
+            notify,
+            shutdown: Shutdown::new(shutdown),
+        }
+    }
+}
+
+/// Self-signed certificate authority, and files signed by it, written to a
+/// temporary directory.
+pub struct Pki {
+    dir: PathBuf,
+    ca: Certificate,
+    ca_key: KeyPair,
+}
+
+impl Pki {
+    pub fn new(name: &str) -> Pki {
+        let dir =
+            std::env::temp_dir().join(format!("mini-redis-tls-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = params.self_signed(&ca_key).unwrap();
+
+        std::fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+
+        Pki { dir, ca, ca_key }
+    }
+
+    pub fn ca_file(&self) -> PathBuf {
+        self.dir.join("ca.pem")
+    }
+
+    /// Issues a certificate for `localhost`, returning its certificate and
+    /// key files.
+    pub fn issue(&self, name: &str) -> (PathBuf, PathBuf) {
+        let key = KeyPair::generate().unwrap();
+        let cert = CertificateParams::new(vec!["localhost".to_string()])
+            .unwrap()
+            .signed_by(&key, &self.ca, &self.ca_key)
+            .unwrap();
+
+        let cert_file = self.dir.join(format!("{}.pem", name));
+        let key_file = self.dir.join(format!("{}.key", name));
+        std::fs::write(&cert_file, cert.pem()).unwrap();
+        std::fs::write(&key_file, key.serialize_pem()).unwrap();
+
+        (cert_file, key_file)
+    }
+}
+
+impl Drop for Pki {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use mini_redis_rs::{join, Connection, Frame, Protocol, ProtocolError, Timeouts};
+use tokio::io::{self, AsyncWriteExt};
+
+#[tokio::test]
+async fn frames_over_an_in_memory_stream() {
+    let (client, server) = tokio::io::duplex(4096);
+    let mut client = Connection::new(client);
+    let mut server = Connection::new(server);
+
+    let cmd = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"SET")),
+        Frame::Bulk(Bytes::from_static(b"key")),
+        Frame::Bulk(Bytes::from(vec![b'x'; 1024])),
+    ]);
+    client.write_frame(&cmd).await.unwrap();
+    assert_eq!(Some(cmd), server.read_frame().await.unwrap());
+
+    server.set_protocol(Protocol::Resp3);
+    server.write_frame(&Frame::Double(0.5)).await.unwrap();
+    assert_eq!(Some(Frame::Double(0.5)), client.read_frame().await.unwrap());
+
+    drop(server);
+    assert_eq!(None, client.read_frame().await.unwrap());
+}
+
+#[tokio::test]
+async fn eof_in_the_middle_of_a_frame() {
+    let (mut client, server) = tokio::io::duplex(64);
+    let mut server = Connection::new(server);
+
+    client.write_all(b"*2\r\n$3\r\nGET\r\n").await.unwrap();
+    drop(client);
+
+    let err = server.read_frame().await.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ProtocolError>(),
+        Some(ProtocolError::UnexpectedEof { offset: 0, .. })
+    ));
+}
+
+#[tokio::test]
+async fn joined_reader_and_writer() {
+    let (reader, mut peer_writer) = tokio::io::duplex(64);
+    let (writer, mut peer_reader) = tokio::io::duplex(64);
+    let mut connection = Connection::new(join(reader, writer));
+
+    peer_writer.write_all(b"PING\r\n").await.unwrap();
+    assert_eq!(
+        Some(Frame::Array(vec![Frame::Bulk(Bytes::from_static(b"PING"))])),
+        connection.read_frame().await.unwrap()
+    );
+
+    connection
+        .write_frame(&Frame::Simple("PONG".to_string()))
+        .await
+        .unwrap();
+
+    let mut peer = Connection::new(join(&mut peer_reader, &mut peer_writer));
+    assert_eq!(
+        Some(Frame::Simple("PONG".to_string())),
+        peer.read_frame().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn queued_frames_are_flushed_before_reading() {
+    let (client, server) = tokio::io::duplex(4096);
+    let mut client = Connection::new(client);
+    let mut server = Connection::new(server);
+
+    let ping = Frame::Array(vec![Frame::Bulk(Bytes::from_static(b"PING"))]);
+    client
+        .write_frames(&[ping.clone(), ping.clone()])
+        .await
+        .unwrap();
+
+    // Both commands are read before any reply is sent.
+    for _ in 0..2 {
+        assert_eq!(Some(ping.clone()), server.read_frame().await.unwrap());
+        server
+            .queue_frame(&Frame::Simple("PONG".to_string()))
+            .await
+            .unwrap();
+    }
+
+    // Running out of frames to read sends the queued replies.
+    let read = tokio::spawn(async move { server.read_frame().await.unwrap() });
+
+    for _ in 0..2 {
+        assert_eq!(
+            Some(Frame::Simple("PONG".to_string())),
+            client.read_frame().await.unwrap()
+        );
+    }
+
+    drop(client);
+    assert_eq!(None, read.await.unwrap());
+}
+
+#[tokio::test]
+async fn idle_and_read_timeouts() {
+    let timeouts = Timeouts {
+        idle: Some(Duration::from_millis(50)),
+        read: Some(Duration::from_millis(50)),
+        write: None,
+    };
+
+    // Nothing is sent at all.
+    let (_client, server) = tokio::io::duplex(64);
+    let mut server = Connection::new(server);
+    server.set_timeouts(timeouts);
+
+    let err = server.read_frame().await.unwrap_err();
+    let err = err.downcast_ref::<io::Error>().unwrap();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    assert_eq!("idle timeout", err.to_string());
+
+    // A frame is started but never finished.
+    let (mut client, server) = tokio::io::duplex(64);
+    let mut server = Connection::new(server);
+    server.set_timeouts(timeouts);
+
+    client.write_all(b"*2\r\n$3\r\nGET\r\n").await.unwrap();
+
+    let err = server.read_frame().await.unwrap_err();
+    let err = err.downcast_ref::<io::Error>().unwrap();
+    assert_eq!("read timeout", err.to_string());
+}
+
+#[tokio::test]
+async fn write_timeout() {
+    // The peer never reads, so the pipe fills up.
+    let (_client, server) = tokio::io::duplex(64);
+    let mut server = Connection::new(server);
+    server.set_timeouts(Timeouts {
+        write: Some(Duration::from_millis(50)),
+        ..Timeouts::default()
+    });
+
+    let reply = Frame::Bulk(Bytes::from(vec![b'x'; 1024]));
+    let err = server.write_frame(&reply).await.unwrap_err();
+    assert_eq!(io::ErrorKind::TimedOut, err.kind());
+}
+
+#[tokio::test]
+async fn closed_keeps_data_sent_in_the_meantime() {
+    let (mut client, server) = tokio::io::duplex(64);
+    let mut server = Connection::new(server);
+
+    client.write_all(b"PING\r\n").await.unwrap();
+    drop(client);
+
+    server.closed().await.unwrap();
+    assert_eq!(
+        Some(Frame::Array(vec![Frame::Bulk(Bytes::from_static(b"PING"))])),
+        server.read_frame().await.unwrap()
+    );
+    assert_eq!(None, server.read_frame().await.unwrap());
+}
+
+#[tokio::test]
+async fn closed_fails_on_too_much_data() {
+    let (mut client, server) = tokio::io::duplex(4096);
+    let mut server = Connection::new(server);
+
+    // The peer keeps sending without ever going away.
+    let sender = tokio::spawn(async move {
+        let chunk = [b'x'; 1024];
+        while client.write_all(&chunk).await.is_ok() {}
+    });
+
+    let err = server.closed().await.unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+    drop(server);
+    sender.await.unwrap();
+}
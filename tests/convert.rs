@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use mini_redis_rs::convert::Error;
+use mini_redis_rs::{Frame, FromFrame, ToFrame};
+
+fn bulk(s: &'static str) -> Frame {
+    Frame::Bulk(Bytes::from_static(s.as_bytes()))
+}
+
+#[test]
+fn values_to_frames() {
+    assert_eq!(Frame::Integer(-3), (-3i32).to_frame());
+    assert_eq!(
+        Frame::BigNumber("18446744073709551615".to_string()),
+        u64::MAX.to_frame()
+    );
+    assert_eq!(bulk("hi"), "hi".to_frame());
+    assert_eq!(Frame::Null, None::<String>.to_frame());
+    assert_eq!(
+        Frame::Array(vec![bulk("SET"), bulk("key"), Frame::Integer(1)]),
+        ("SET", "key", 1).to_frame()
+    );
+
+    let map: HashMap<_, _> = [("a", vec![1.5])].into_iter().collect();
+    assert_eq!(
+        Frame::Map(vec![(bulk("a"), Frame::Array(vec![Frame::Double(1.5)]))]),
+        map.to_frame()
+    );
+}
+
+#[test]
+fn values_from_frames() {
+    assert_eq!(Ok(42), i64::from_frame(Frame::Integer(42)));
+    assert_eq!(Ok(42), u8::from_frame(bulk("42")));
+    assert_eq!(Ok(2.5), f64::from_frame(bulk("2.5")));
+    assert_eq!(Ok(true), bool::from_frame(Frame::Integer(1)));
+    assert_eq!(Ok(None), Option::<String>::from_frame(Frame::Null));
+    assert_eq!(
+        Ok("OK".to_string()),
+        String::from_frame(Frame::Simple("OK".to_string()))
+    );
+
+    let data = Bytes::from_static(b"payload");
+    let frame = Bytes::from_frame(Frame::Bulk(data.clone())).unwrap();
+    assert_eq!(data.as_ptr(), frame.as_ptr());
+
+    let frame = Frame::Array(vec![bulk("x"), Frame::Integer(7)]);
+    assert_eq!(
+        Ok(("x".to_string(), 7u32)),
+        <(String, u32)>::from_frame(frame)
+    );
+}
+
+#[test]
+fn maps_from_either_protocol() {
+    let expected: HashMap<String, i64> = [("a".to_string(), 1), ("b".to_string(), 2)]
+        .into_iter()
+        .collect();
+
+    let resp3 = Frame::Map(vec![
+        (bulk("a"), Frame::Integer(1)),
+        (bulk("b"), Frame::Integer(2)),
+    ]);
+    assert_eq!(Ok(expected.clone()), HashMap::from_frame(resp3.clone()));
+
+    let resp2 = Frame::Array(vec![bulk("a"), bulk("1"), bulk("b"), bulk("2")]);
+    assert_eq!(Ok(expected), HashMap::from_frame(resp2));
+
+    assert_eq!(
+        Ok(vec![("a".to_string(), 1), ("b".to_string(), 2)]),
+        Vec::<(String, i64)>::from_frame(resp3)
+    );
+}
+
+#[test]
+fn conversion_errors() {
+    assert_eq!(
+        Err(Error::Reply("ERR nope".to_string())),
+        String::from_frame(Frame::Error("ERR nope".to_string()))
+    );
+    assert_eq!(
+        Err(Error::UnexpectedFrame {
+            expected: "i64",
+            found: "array",
+        }),
+        i64::from_frame(Frame::Array(vec![]))
+    );
+    assert_eq!(
+        Err(Error::InvalidValue {
+            expected: "u8",
+            value: "256".to_string(),
+        }),
+        u8::from_frame(Frame::Integer(256))
+    );
+    assert_eq!(
+        Err(Error::InvalidLength {
+            expected: 2,
+            found: 1,
+        }),
+        <(i64, i64)>::from_frame(Frame::Array(vec![Frame::Integer(1)]))
+    );
+}
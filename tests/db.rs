@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use mini_redis_rs::db::{Condition, End, Expiry};
+use mini_redis_rs::{Db, ToFrame, Value, WrongType};
+use tokio::time::{self, Instant};
+
+fn value(s: &'static str) -> Bytes {
+    Bytes::from_static(s.as_bytes())
+}
+
+#[tokio::test]
+async fn conditional_set() {
+    let db = Db::new();
+    let set = |value, condition| {
+        db.set("key".to_string(), value, Expiry::Persist, condition, false)
+            .unwrap()
+    };
+
+    assert_eq!((false, None), set(value("a"), Condition::IfPresent));
+    assert_eq!((true, None), set(value("a"), Condition::IfAbsent));
+    assert_eq!(
+        (false, Some(value("a"))),
+        set(value("b"), Condition::IfAbsent)
+    );
+    assert_eq!((true, Some(value("a"))), set(value("b"), Condition::Always));
+    assert_eq!(Ok(Some(value("b"))), db.get("key"));
+}
+
+#[tokio::test]
+async fn keys_expire() {
+    let db = Db::new();
+    let soon = Instant::now() + Duration::from_millis(50);
+
+    db.set(
+        "a".to_string(),
+        value("1"),
+        Expiry::At(soon),
+        Condition::Always,
+        false,
+    )
+    .unwrap();
+    db.set(
+        "b".to_string(),
+        value("2"),
+        Expiry::At(soon),
+        Condition::Always,
+        false,
+    )
+    .unwrap();
+    db.set(
+        "c".to_string(),
+        value("3"),
+        Expiry::Persist,
+        Condition::Always,
+        false,
+    )
+    .unwrap();
+    assert!(matches!(db.ttl("a"), Some(Some(ttl)) if ttl <= Duration::from_millis(50)));
+    assert_eq!(Some(None), db.ttl("c"));
+
+    // Setting a key again clears its expiration, unless it is kept.
+    db.set(
+        "b".to_string(),
+        value("2"),
+        Expiry::Keep,
+        Condition::Always,
+        false,
+    )
+    .unwrap();
+    assert!(db.persist("a"));
+    assert!(!db.persist("a"));
+
+    time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(Ok(Some(value("1"))), db.get("a"));
+    assert_eq!(Ok(None), db.get("b"));
+    assert_eq!(None, db.ttl("b"));
+    assert!(!db.expire("b", Instant::now()));
+
+    // Expiring in the past deletes the key.
+    assert!(db.expire("c", Instant::now()));
+    assert_eq!(Ok(None), db.get("c"));
+}
+
+#[tokio::test]
+async fn dump_keeps_expirations() {
+    let db = Db::new();
+    let later = Instant::now() + Duration::from_secs(60);
+
+    db.set(
+        "a".to_string(),
+        value("1"),
+        Expiry::At(later),
+        Condition::Always,
+        false,
+    )
+    .unwrap();
+
+    let commands: Vec<_> = db.dump().flatten().collect();
+    assert_eq!(1, commands.len());
+    assert!(commands[0].to_string().contains("\"PXAT\""));
+}
+
+#[tokio::test]
+async fn values_have_a_type() {
+    let db = Db::new();
+    let list = Value::List([value("a"), value("b")].into_iter().collect());
+
+    db.set_value("list".to_string(), list);
+    assert_eq!(Some("list"), db.value_type("list"));
+    assert_eq!(None, db.value_type("missing"));
+
+    // String commands fail on other kinds of values, except for a plain SET,
+    // which replaces them.
+    assert_eq!(Err(WrongType), db.get("list"));
+    assert_eq!(Err(WrongType), db.get_ex("list", Expiry::Persist));
+    assert_eq!(
+        Err(WrongType),
+        db.set(
+            "list".to_string(),
+            value("1"),
+            Expiry::Persist,
+            Condition::Always,
+            true,
+        )
+    );
+    assert_eq!(
+        Ok((false, None)),
+        db.set(
+            "list".to_string(),
+            value("1"),
+            Expiry::Persist,
+            Condition::IfAbsent,
+            false,
+        )
+    );
+
+    let commands: Vec<_> = db.dump().flatten().collect();
+    assert_eq!(vec![("RPUSH", "list", "a", "b").to_frame()], commands);
+
+    assert_eq!(
+        Ok((true, None)),
+        db.set(
+            "list".to_string(),
+            value("1"),
+            Expiry::Persist,
+            Condition::Always,
+            false,
+        )
+    );
+    assert_eq!(Some("string"), db.value_type("list"));
+}
+
+#[tokio::test]
+async fn lists() {
+    let db = Db::new();
+    let values = |values: &[&'static str]| values.iter().map(|v| value(v)).collect::<Vec<_>>();
+
+    assert_eq!(
+        Ok(3),
+        db.push("l".to_string(), End::Left, values(&["c", "b", "a"]))
+    );
+    assert_eq!(
+        Ok(5),
+        db.push("l".to_string(), End::Right, values(&["d", "e"]))
+    );
+    assert_eq!(
+        Ok(values(&["a", "b", "c", "d", "e"])),
+        db.lrange("l", 0, -1)
+    );
+
+    // Negative indexes count from the end, and ranges are clamped.
+    assert_eq!(Ok(values(&["d", "e"])), db.lrange("l", -2, 100));
+    assert_eq!(Ok(values(&[])), db.lrange("l", 3, 1));
+    assert_eq!(Ok(Some(value("e"))), db.lindex("l", -1));
+    assert_eq!(Ok(None), db.lindex("l", 5));
+    assert_eq!(Ok(Some(true)), db.lset("l", -5, value("A")));
+    assert_eq!(Ok(Some(false)), db.lset("l", -6, value("A")));
+    assert_eq!(Ok(None), db.lset("missing", 0, value("A")));
+
+    assert_eq!(Ok(Some(values(&["e", "d"]))), db.pop("l", End::Right, 2));
+    assert_eq!(Ok(Some(values(&["A"]))), db.pop("l", End::Left, 1));
+    assert_eq!(Ok(None), db.pop("missing", End::Left, 1));
+
+    db.push(
+        "r".to_string(),
+        End::Right,
+        values(&["x", "y", "x", "z", "x"]),
+    )
+    .unwrap();
+    assert_eq!(Ok(2), db.lrem("r", -2, b"x"));
+    assert_eq!(Ok(values(&["x", "y", "z"])), db.lrange("r", 0, -1));
+    assert_eq!(Ok(()), db.ltrim("r", 1, -1));
+    assert_eq!(Ok(values(&["y", "z"])), db.lrange("r", 0, -1));
+
+    // Moving between lists, and rotating a list.
+    assert_eq!(
+        Ok(Some(value("c"))),
+        db.lmove("l", "r", End::Right, End::Left)
+    );
+    assert_eq!(
+        Ok(Some(value("z"))),
+        db.lmove("r", "r", End::Right, End::Left)
+    );
+    assert_eq!(Ok(values(&["z", "c", "y"])), db.lrange("r", 0, -1));
+    assert_eq!(Ok(None), db.lmove("missing", "r", End::Left, End::Left));
+
+    // Lists are deleted once empty.
+    assert_eq!(
+        Ok(Some(value("b"))),
+        db.lmove("l", "r", End::Left, End::Left)
+    );
+    assert_eq!(None, db.value_type("l"));
+    assert_eq!(Ok(0), db.llen("l"));
+    assert_eq!(Ok(()), db.ltrim("r", 5, 10));
+    assert_eq!(None, db.value_type("r"));
+
+    // A move to a key of another kind does not pop anything.
+    db.push("l".to_string(), End::Left, values(&["a"])).unwrap();
+    db.set(
+        "s".to_string(),
+        value("1"),
+        Expiry::Persist,
+        Condition::Always,
+        false,
+    )
+    .unwrap();
+    assert_eq!(Err(WrongType), db.lmove("l", "s", End::Left, End::Left));
+    assert_eq!(
+        Err(WrongType),
+        db.push("s".to_string(), End::Left, values(&["a"]))
+    );
+    assert_eq!(Ok(1), db.llen("l"));
+}
@@ -0,0 +1,319 @@
+use std::io::Cursor;
+
+use bytes::{Bytes, BytesMut};
+use mini_redis_rs::frame::{Error, Limit};
+use mini_redis_rs::{Frame, Protocol, ProtocolError, ProtocolLimits};
+
+/// Encodes `frame`, then checks and parses it back.
+fn round_trip(frame: &Frame, protocol: Protocol) -> Frame {
+    let mut buf = BytesMut::new();
+    frame.encode(&mut buf, protocol);
+
+    let mut src = Cursor::new(&buf[..]);
+    Frame::check(&mut src).unwrap();
+    assert_eq!(buf.len(), src.position() as usize);
+
+    let mut src = buf.freeze();
+    let frame = Frame::parse(&mut src).unwrap();
+    assert!(src.is_empty());
+
+    frame
+}
+
+fn bulk(s: &'static str) -> Frame {
+    Frame::Bulk(Bytes::from_static(s.as_bytes()))
+}
+
+#[test]
+fn nested_arrays_round_trip() {
+    let frame = Frame::Array(vec![
+        bulk("one"),
+        Frame::Array(vec![
+            Frame::Integer(-2),
+            Frame::Array(vec![Frame::Null, bulk("")]),
+            Frame::Array(vec![]),
+        ]),
+        Frame::Simple("OK".to_string()),
+        Frame::Error("ERR nested".to_string()),
+    ]);
+
+    assert_eq!(frame, round_trip(&frame, Protocol::Resp2));
+    assert_eq!(frame, round_trip(&frame, Protocol::Resp3));
+}
+
+#[test]
+fn resp3_aggregates_round_trip() {
+    let frame = Frame::Push(vec![
+        bulk("message"),
+        Frame::Map(vec![
+            (
+                bulk("set"),
+                Frame::Set(vec![Frame::Integer(1), Frame::Boolean(true)]),
+            ),
+            (
+                Frame::Double(1.5),
+                Frame::BigNumber("-12345678901234567890".to_string()),
+            ),
+            (
+                bulk("text"),
+                Frame::Verbatim("txt".to_string(), Bytes::from_static(b"hi")),
+            ),
+        ]),
+        Frame::Attribute(vec![(bulk("ttl"), Frame::Null)]),
+    ]);
+
+    assert_eq!(frame, round_trip(&frame, Protocol::Resp3));
+}
+
+#[test]
+fn resp3_aggregates_downgrade_to_resp2() {
+    let frame = Frame::Array(vec![
+        Frame::Map(vec![(bulk("proto"), Frame::Integer(2))]),
+        Frame::Set(vec![Frame::Boolean(false)]),
+        Frame::Double(f64::INFINITY),
+    ]);
+
+    let expected = Frame::Array(vec![
+        Frame::Array(vec![bulk("proto"), Frame::Integer(2)]),
+        Frame::Array(vec![Frame::Integer(0)]),
+        bulk("inf"),
+    ]);
+
+    assert_eq!(expected, round_trip(&frame, Protocol::Resp2));
+}
+
+#[test]
+fn nested_attributes_are_dropped_in_resp2() {
+    let frame = Frame::Array(vec![
+        Frame::Attribute(vec![(bulk("ttl"), Frame::Integer(3600))]),
+        bulk("value"),
+        Frame::Map(vec![(
+            bulk("key"),
+            Frame::Array(vec![Frame::Attribute(vec![]), Frame::Integer(1)]),
+        )]),
+        Frame::Set(vec![Frame::Attribute(vec![])]),
+    ]);
+
+    let expected = Frame::Array(vec![
+        bulk("value"),
+        Frame::Array(vec![bulk("key"), Frame::Array(vec![Frame::Integer(1)])]),
+        Frame::Array(vec![]),
+    ]);
+
+    assert_eq!(expected, round_trip(&frame, Protocol::Resp2));
+    assert_eq!(frame, round_trip(&frame, Protocol::Resp3));
+}
+
+#[test]
+fn null_array_encoding() {
+    let mut buf = BytesMut::new();
+    Frame::NullArray.encode(&mut buf, Protocol::Resp2);
+    assert_eq!(&buf[..], b"*-1\r\n");
+
+    // RESP3 only has one null.
+    buf.clear();
+    Frame::NullArray.encode(&mut buf, Protocol::Resp3);
+    assert_eq!(&buf[..], b"_\r\n");
+
+    assert_eq!(Frame::Null, round_trip(&Frame::NullArray, Protocol::Resp2));
+}
+
+#[test]
+fn error_uses_minus_prefix() {
+    let mut buf = BytesMut::new();
+    Frame::Error("ERR boom".to_string()).encode(&mut buf, Protocol::Resp2);
+
+    assert_eq!(&buf[..], b"-ERR boom\r\n");
+}
+
+#[test]
+fn line_breaks_in_lines_are_replaced() {
+    let mut buf = BytesMut::new();
+    Frame::Error("ERR a\r\n+OK".to_string()).encode(&mut buf, Protocol::Resp2);
+    Frame::Simple("b\nc".to_string()).encode(&mut buf, Protocol::Resp2);
+
+    assert_eq!(&buf[..], b"-ERR a  +OK\r\n+b c\r\n");
+}
+
+#[test]
+fn inline_command_with_quoted_arguments() {
+    let buf = b"SET \"hello world\" 'it\\'s' \"\\x41\\n\"\r\n";
+    Frame::check(&mut Cursor::new(&buf[..])).unwrap();
+
+    let mut src = Bytes::from_static(buf);
+
+    let expected = Frame::Array(vec![
+        bulk("SET"),
+        bulk("hello world"),
+        bulk("it's"),
+        bulk("A\n"),
+    ]);
+    assert_eq!(expected, Frame::parse(&mut src).unwrap());
+}
+
+#[test]
+fn inline_command_errors() {
+    let mut src = Cursor::new(&b"GET \"unterminated\r\n"[..]);
+    assert!(matches!(
+        Frame::check(&mut src),
+        Err(Error::Protocol(ProtocolError::UnbalancedQuotes {
+            offset: 0,
+            ..
+        }))
+    ));
+
+    let line = vec![b'a'; 64 * 1024 + 1];
+    let mut src = Cursor::new(&line[..]);
+    assert!(matches!(
+        Frame::check(&mut src),
+        Err(Error::Protocol(ProtocolError::LimitExceeded {
+            limit: Limit::InlineLen,
+            ..
+        }))
+    ));
+}
+
+#[test]
+fn bulks_are_sliced_from_the_source_buffer() {
+    let mut src = Bytes::from_static(b"*2\r\n$3\r\nfoo\r\n$5\r\nhello\r\n:1\r\n");
+    let range = src.as_ptr_range();
+
+    let frame = Frame::parse(&mut src).unwrap();
+    assert_eq!(frame, Frame::Array(vec![bulk("foo"), bulk("hello")]));
+
+    // The payloads point into the original buffer rather than a copy.
+    if let Frame::Array(parts) = frame {
+        for part in parts {
+            if let Frame::Bulk(data) = part {
+                assert!(range.contains(&data.as_ptr()));
+            }
+        }
+    }
+
+    assert_eq!(&src[..], b":1\r\n");
+}
+
+#[test]
+fn limits_are_enforced_before_the_data_arrives() {
+    let limits = ProtocolLimits {
+        max_bulk_len: 16,
+        max_multibulk_len: 4,
+        max_depth: 2,
+        ..ProtocolLimits::default()
+    };
+
+    let check = |buf: &[u8]| match Frame::check_with_limits(&mut Cursor::new(buf), &limits) {
+        Err(Error::Protocol(ProtocolError::LimitExceeded {
+            limit,
+            offset,
+            path,
+        })) => Some((limit, offset, path)),
+        Err(Error::Incomplete) | Ok(_) => None,
+        Err(err) => panic!("unexpected error: {}", err),
+    };
+
+    assert_eq!(Some((Limit::BulkLen, 0, vec![])), check(b"$17\r\n"));
+    assert_eq!(
+        Some((Limit::MultibulkLen, 4, vec![0])),
+        check(b"*1\r\n*5\r\n")
+    );
+    assert_eq!(
+        Some((Limit::Depth, 8, vec![0, 0])),
+        check(b"*1\r\n*1\r\n*1\r\n")
+    );
+
+    assert_eq!(None, check(b"$16\r\n"));
+    assert_eq!(None, check(b"*1\r\n*1\r\n:1\r\n"));
+}
+
+#[test]
+fn errors_are_located() {
+    let buf = b"*3\r\n:1\r\n*2\r\n+ok\r\n:x\r\n";
+    let err = match Frame::check(&mut Cursor::new(&buf[..])) {
+        Err(Error::Protocol(err)) => err,
+        res => panic!("unexpected result: {:?}", res),
+    };
+
+    assert_eq!(
+        ProtocolError::InvalidFormat {
+            offset: 17,
+            path: vec![1, 1],
+        },
+        err
+    );
+    assert_eq!(
+        Frame::Error("ERR Protocol error: invalid frame format".to_string()),
+        err.to_frame()
+    );
+    assert_eq!(
+        "protocol error; invalid frame format at byte 17 in frame [1, 1]",
+        err.to_string()
+    );
+}
+
+#[test]
+fn huge_lengths_within_lax_limits() {
+    let limits = ProtocolLimits {
+        max_bulk_len: usize::MAX,
+        ..ProtocolLimits::default()
+    };
+
+    // The largest length a header can hold is waited for, or rejected where
+    // it does not fit in a `usize` along with the trailing "\r\n".
+    match Frame::check_with_limits(&mut Cursor::new(&b"$9223372036854775807\r\n"[..]), &limits) {
+        Err(Error::Incomplete) | Err(Error::Protocol(ProtocolError::BadLength { .. })) => {}
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
+
+#[test]
+fn display_like_redis_cli() {
+    let frame = Frame::Array(vec![
+        bulk("first"),
+        Frame::Integer(2),
+        Frame::Array(vec![
+            Frame::Bulk(Bytes::from_static(b"a\"b\\\n\x01\xff")),
+            Frame::Null,
+            Frame::Array(vec![]),
+        ]),
+        Frame::Error("ERR nope".to_string()),
+        Frame::Simple("OK".to_string()),
+        Frame::Map(vec![(bulk("k"), Frame::Double(1.5))]),
+        Frame::Boolean(true),
+        Frame::Set(vec![Frame::Integer(1)]),
+        bulk("8"),
+        bulk("9"),
+        bulk("10"),
+    ]);
+
+    let expected = [
+        r#" 1) "first""#,
+        r#" 2) (integer) 2"#,
+        r#" 3) 1) "a\"b\\\n\x01\xff""#,
+        r#"    2) (nil)"#,
+        r#"    3) (empty array)"#,
+        r#" 4) (error) ERR nope"#,
+        r#" 5) OK"#,
+        r#" 6) 1# "k" => (double) 1.5"#,
+        r#" 7) (true)"#,
+        r#" 8) 1~ (integer) 1"#,
+        r#" 9) "8""#,
+        r#"10) "9""#,
+        r#"11) "10""#,
+    ]
+    .join("\n");
+
+    assert_eq!(expected, frame.to_string());
+}
+
+#[test]
+fn display_raw() {
+    let frame = Frame::Array(vec![
+        bulk("first"),
+        Frame::Integer(2),
+        Frame::Null,
+        Frame::Map(vec![(bulk("k"), bulk("v"))]),
+    ]);
+
+    assert_eq!("first\n2\n\nk v", frame.raw().to_string());
+}
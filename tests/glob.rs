@@ -0,0 +1,46 @@
+use mini_redis_rs::glob::glob_match;
+
+fn matches(pattern: &str, string: &str) -> bool {
+    glob_match(pattern.as_bytes(), string.as_bytes())
+}
+
+#[test]
+fn wildcards() {
+    assert!(matches("invalidate:*", "invalidate:users:1"));
+    assert!(matches("invalidate:*", "invalidate:"));
+    assert!(!matches("invalidate:*", "invalidated"));
+    assert!(matches("*", ""));
+    assert!(matches("*:*:1", "a:b:c:1"));
+    assert!(!matches("*:*:1", "a:b:c:2"));
+    assert!(matches("h?llo", "hello"));
+    assert!(!matches("h?llo", "hllo"));
+    assert!(matches("a**b", "axyzb"));
+    assert!(!matches("abc", "ab"));
+    assert!(!matches("ab", "abc"));
+}
+
+#[test]
+fn classes() {
+    assert!(matches("h[ae]llo", "hallo"));
+    assert!(!matches("h[ae]llo", "hillo"));
+    assert!(matches("h[^e]llo", "hallo"));
+    assert!(!matches("h[^e]llo", "hello"));
+    assert!(matches("h[a-b]llo", "hbllo"));
+    assert!(matches("h[b-a]llo", "hallo"));
+    assert!(!matches("h[a-b]llo", "hcllo"));
+    assert!(matches("[\\]]", "]"));
+
+    // An unterminated class extends to the end of the pattern.
+    assert!(matches("a[bc", "ac"));
+}
+
+#[test]
+fn escapes() {
+    assert!(matches("a\\*b", "a*b"));
+    assert!(!matches("a\\*b", "axb"));
+    assert!(matches("\\?", "?"));
+    assert!(!matches("\\?", "x"));
+
+    // A trailing backslash matches itself.
+    assert!(matches("a\\", "a\\"));
+}
@@ -0,0 +1,104 @@
+use bytes::{Bytes, BytesMut};
+use mini_redis_rs::{Frame, Parser, Protocol, ProtocolError, ProtocolLimits};
+
+#[test]
+fn resumes_across_partial_reads() {
+    let frame = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"SET")),
+        Frame::Map(vec![(
+            Frame::Integer(1),
+            Frame::Bulk(Bytes::from(vec![7; 300])),
+        )]),
+        Frame::Array(vec![]),
+        Frame::Null,
+    ]);
+
+    let mut encoded = BytesMut::new();
+    frame.encode(&mut encoded, Protocol::Resp3);
+    frame.encode(&mut encoded, Protocol::Resp3);
+
+    // Feed the two frames one byte at a time.
+    let mut parser = Parser::default();
+    let mut buf = BytesMut::new();
+    let mut frames = vec![];
+
+    for byte in encoded.iter() {
+        buf.extend_from_slice(&[*byte]);
+
+        if let Some(frame) = parser.parse(&mut buf).unwrap() {
+            frames.push(frame);
+        }
+    }
+
+    assert_eq!(vec![frame.clone(), frame], frames);
+    assert!(buf.is_empty());
+    assert!(!parser.has_partial_frame());
+}
+
+#[test]
+fn partial_frame_is_tracked() {
+    let mut parser = Parser::default();
+    let mut buf = BytesMut::from(&b"*2\r\n$3\r\nGET\r\n$5\r\nhel"[..]);
+
+    assert!(parser.parse(&mut buf).unwrap().is_none());
+    assert!(parser.has_partial_frame());
+
+    buf.extend_from_slice(b"lo\r\nPING\r\n");
+
+    let expected = Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(b"GET")),
+        Frame::Bulk(Bytes::from_static(b"hello")),
+    ]);
+    assert_eq!(Some(expected), parser.parse(&mut buf).unwrap());
+
+    let inline = Frame::Array(vec![Frame::Bulk(Bytes::from_static(b"PING"))]);
+    assert_eq!(Some(inline), parser.parse(&mut buf).unwrap());
+}
+
+#[test]
+fn errors_are_located_in_the_stream() {
+    let mut parser = Parser::default();
+    let mut buf = BytesMut::from(&b"*1\r\n$4\r\nPING\r\n*2\r\n$3\r\nGET\r\n$"[..]);
+
+    assert!(parser.parse(&mut buf).unwrap().is_some());
+    assert!(parser.parse(&mut buf).unwrap().is_none());
+
+    buf.extend_from_slice(b"x\r\n");
+
+    assert_eq!(
+        ProtocolError::BadLength {
+            offset: 27,
+            path: vec![1],
+        },
+        parser.parse(&mut buf).unwrap_err()
+    );
+}
+
+#[test]
+fn eof_within_a_frame() {
+    let mut parser = Parser::default();
+    let mut buf = BytesMut::from(&b"*2\r\n$3\r\nGET\r\n$5\r\nhel"[..]);
+
+    assert!(parser.parse(&mut buf).unwrap().is_none());
+    assert_eq!(
+        Some(ProtocolError::UnexpectedEof {
+            offset: 13,
+            path: vec![1],
+        }),
+        parser.eof(buf.len())
+    );
+}
+
+#[test]
+fn huge_lengths_within_lax_limits() {
+    let mut parser = Parser::new(ProtocolLimits {
+        max_bulk_len: usize::MAX,
+        ..ProtocolLimits::default()
+    });
+    let mut buf = BytesMut::from(&b"$9223372036854775807\r\n"[..]);
+
+    match parser.parse(&mut buf) {
+        Ok(None) | Err(ProtocolError::BadLength { .. }) => {}
+        res => panic!("unexpected result: {:?}", res),
+    }
+}
@@ -0,0 +1,328 @@
+mod common;
+
+use bytes::Bytes;
+use common::{bulk, command, Fixture};
+use mini_redis_rs::{Command, Frame};
+
+/// Returns a frame pushed in subscriber mode, as received by RESP2 clients,
+/// which get arrays instead of pushes.
+fn push(kind: &'static str, channel: &'static str, value: Frame) -> Option<Frame> {
+    Some(Frame::Array(vec![bulk(kind), bulk(channel), value]))
+}
+
+#[tokio::test]
+async fn subscriber_mode() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    let subscriber = tokio::spawn({
+        let db = db.clone();
+        let cmd = Command::from_frame(command(&["SUBSCRIBE", "news"])).unwrap();
+
+        async move { cmd.apply(&db, &mut server, &mut shutdown).await }
+    });
+
+    assert_eq!(
+        push("subscribe", "news", Frame::Integer(1)),
+        client.read_frame().await.unwrap()
+    );
+
+    assert_eq!(1, db.publish("news", Bytes::from_static(b"hello")));
+    assert_eq!(0, db.publish("sports", Bytes::from_static(b"goal")));
+
+    assert_eq!(
+        push("message", "news", bulk("hello")),
+        client.read_frame().await.unwrap()
+    );
+
+    // Other commands are rejected, without leaving subscriber mode.
+    client.write_frame(&command(&["GET", "key"])).await.unwrap();
+    assert!(matches!(
+        client.read_frame().await.unwrap(),
+        Some(Frame::Error(err)) if err.starts_with("ERR Can't execute 'get'")
+    ));
+
+    drop(notify);
+    subscriber.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn unsubscribing_from_every_channel_leaves_subscriber_mode() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    client
+        .write_frame(&command(&["UNSUBSCRIBE"]))
+        .await
+        .unwrap();
+
+    let cmd = Command::from_frame(command(&["SUBSCRIBE", "a", "b"])).unwrap();
+    cmd.apply(&db, &mut server, &mut shutdown).await.unwrap();
+    server.flush().await.unwrap();
+
+    let expected = [
+        push("subscribe", "a", Frame::Integer(1)),
+        push("subscribe", "b", Frame::Integer(2)),
+        push("unsubscribe", "a", Frame::Integer(1)),
+        push("unsubscribe", "b", Frame::Integer(0)),
+    ];
+
+    for expected in expected {
+        assert_eq!(expected, client.read_frame().await.unwrap());
+    }
+
+    assert_eq!(0, db.publish("a", Bytes::from_static(b"gone")));
+}
+
+#[tokio::test]
+async fn pattern_subscriptions() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    let subscriber = tokio::spawn({
+        let db = db.clone();
+        let cmd = Command::from_frame(command(&["PSUBSCRIBE", "news.*"])).unwrap();
+
+        async move { cmd.apply(&db, &mut server, &mut shutdown).await }
+    });
+
+    assert_eq!(
+        push("psubscribe", "news.*", Frame::Integer(1)),
+        client.read_frame().await.unwrap()
+    );
+
+    client
+        .write_frame(&command(&["SUBSCRIBE", "news.tech"]))
+        .await
+        .unwrap();
+    assert_eq!(
+        push("subscribe", "news.tech", Frame::Integer(2)),
+        client.read_frame().await.unwrap()
+    );
+
+    // The message is received once per matching subscription.
+    assert_eq!(2, db.publish("news.tech", Bytes::from_static(b"rust")));
+    assert_eq!(0, db.publish("sports", Bytes::from_static(b"goal")));
+
+    let mut received = vec![
+        client.read_frame().await.unwrap(),
+        client.read_frame().await.unwrap(),
+    ];
+    received.sort_by_key(|frame| format!("{:?}", frame));
+
+    assert_eq!(
+        vec![
+            push("message", "news.tech", bulk("rust")),
+            Some(Frame::Array(vec![
+                bulk("pmessage"),
+                bulk("news.*"),
+                bulk("news.tech"),
+                bulk("rust"),
+            ])),
+        ],
+        received
+    );
+
+    // PUNSUBSCRIBE without arguments leaves channel subscriptions alone.
+    client
+        .write_frame(&command(&["PUNSUBSCRIBE"]))
+        .await
+        .unwrap();
+    assert_eq!(
+        push("punsubscribe", "news.*", Frame::Integer(1)),
+        client.read_frame().await.unwrap()
+    );
+    assert_eq!(1, db.publish("news.tech", Bytes::from_static(b"go")));
+
+    drop(notify);
+    subscriber.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn pubsub_introspection() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    let _news = db.subscribe("news".to_string());
+    let _sports = [
+        db.subscribe("sports".to_string()),
+        db.subscribe("sports".to_string()),
+    ];
+    let _pattern = db.psubscribe("n*".to_string());
+
+    let commands = [
+        command(&["PUBSUB", "CHANNELS", "n*"]),
+        command(&["PUBSUB", "NUMSUB", "sports", "weather"]),
+        command(&["PUBSUB", "NUMPAT"]),
+    ];
+
+    for cmd in commands {
+        let cmd = Command::from_frame(cmd).unwrap();
+        cmd.apply(&db, &mut server, &mut shutdown).await.unwrap();
+    }
+    server.flush().await.unwrap();
+
+    let expected = [
+        Frame::Array(vec![bulk("news")]),
+        Frame::Array(vec![
+            bulk("sports"),
+            Frame::Integer(2),
+            bulk("weather"),
+            Frame::Integer(0),
+        ]),
+        Frame::Integer(1),
+    ];
+
+    for expected in expected {
+        assert_eq!(Some(expected), client.read_frame().await.unwrap());
+    }
+
+    let err = Command::from_frame(command(&["PUBSUB", "NUMPAT", "x"])).unwrap_err();
+    assert_eq!(
+        Frame::Error("ERR wrong number of arguments for 'pubsub|numpat' command".to_string()),
+        err.to_frame()
+    );
+}
+
+/// Subscribes with `subscribe`, sends `unsubscribe` which has nothing to
+/// unsubscribe from, and returns the reply to it.
+async fn unsubscribe_from_none(subscribe: &[&str], unsubscribe: &[&str]) -> Option<Frame> {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    let subscriber = tokio::spawn({
+        let cmd = Command::from_frame(command(subscribe)).unwrap();
+
+        async move { cmd.apply(&db, &mut server, &mut shutdown).await }
+    });
+    assert!(client.read_frame().await.unwrap().is_some());
+
+    client.write_frame(&command(unsubscribe)).await.unwrap();
+    let reply = client.read_frame().await.unwrap();
+
+    drop(notify);
+    subscriber.await.unwrap().unwrap();
+    reply
+}
+
+#[tokio::test]
+async fn punsubscribe_without_patterns() {
+    assert_eq!(
+        Some(Frame::Array(vec![
+            bulk("punsubscribe"),
+            Frame::Null,
+            Frame::Integer(1)
+        ])),
+        unsubscribe_from_none(&["SUBSCRIBE", "news"], &["PUNSUBSCRIBE"]).await
+    );
+}
+
+#[tokio::test]
+async fn unsubscribe_without_channels() {
+    assert_eq!(
+        Some(Frame::Array(vec![
+            bulk("unsubscribe"),
+            Frame::Null,
+            Frame::Integer(1)
+        ])),
+        unsubscribe_from_none(&["PSUBSCRIBE", "news.*"], &["UNSUBSCRIBE"]).await
+    );
+}
+
+#[tokio::test]
+async fn ping_in_subscriber_mode() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    let subscriber = tokio::spawn({
+        let cmd = Command::from_frame(command(&["SUBSCRIBE", "news"])).unwrap();
+
+        async move { cmd.apply(&db, &mut server, &mut shutdown).await }
+    });
+    assert!(client.read_frame().await.unwrap().is_some());
+
+    // RESP2 clients get a push, which they receive as an array like messages.
+    client
+        .write_frames(&[command(&["PING"]), command(&["PING", "hi"])])
+        .await
+        .unwrap();
+    assert_eq!(
+        Some(Frame::Array(vec![bulk("pong"), bulk("")])),
+        client.read_frame().await.unwrap()
+    );
+    assert_eq!(
+        Some(Frame::Array(vec![bulk("pong"), bulk("hi")])),
+        client.read_frame().await.unwrap()
+    );
+
+    drop(notify);
+    subscriber.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn slow_subscribers_are_disconnected() {
+    let Fixture {
+        mut client,
+        mut server,
+        db,
+        notify: _notify,
+        mut shutdown,
+    } = Fixture::new();
+
+    let subscriber = tokio::spawn({
+        let db = db.clone();
+        let cmd = Command::from_frame(command(&["SUBSCRIBE", "news"])).unwrap();
+
+        async move { cmd.apply(&db, &mut server, &mut shutdown).await }
+    });
+    assert!(client.read_frame().await.unwrap().is_some());
+
+    // The client does not read the messages, so they pile up until some of
+    // them are dropped.
+    for i in 0..5000 {
+        db.publish("news", Bytes::from(i.to_string()));
+        tokio::task::yield_now().await;
+    }
+
+    let err = loop {
+        match client.read_frame().await.unwrap() {
+            Some(Frame::Error(err)) => break err,
+            Some(_) => {}
+            None => panic!("disconnected without being told why"),
+        }
+    };
+    assert!(err.starts_with("ERR subscriber too slow"), "{}", err);
+
+    assert!(subscriber.await.unwrap().is_err());
+    assert_eq!(0, db.publish("news", Bytes::from_static(b"gone")));
+}
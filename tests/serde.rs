@@ -0,0 +1,102 @@
+#![cfg(feature = "serde")]
+
+use bytes::Bytes;
+use mini_redis_rs::serde_frame::{from_frame, to_frame};
+use mini_redis_rs::Frame;
+use serde::{Deserialize, Serialize};
+
+fn bulk(s: &'static str) -> Frame {
+    Frame::Bulk(Bytes::from_static(s.as_bytes()))
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct User {
+    name: String,
+    visits: u64,
+    admin: bool,
+    email: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Event {
+    Joined,
+    Visit(u32),
+    Moved { from: String, to: String },
+}
+
+#[test]
+fn structs_round_trip_as_maps() {
+    let user = User {
+        name: "ferris".to_string(),
+        visits: 12,
+        admin: false,
+        email: None,
+    };
+
+    let frame = to_frame(&user).unwrap();
+    assert_eq!(
+        Frame::Map(vec![
+            (bulk("name"), bulk("ferris")),
+            (bulk("visits"), Frame::Integer(12)),
+            (bulk("admin"), Frame::Boolean(false)),
+            (bulk("email"), Frame::Null),
+        ]),
+        frame
+    );
+    assert_eq!(user, from_frame(frame).unwrap());
+}
+
+#[test]
+fn structs_from_resp2_replies() {
+    let reply = Frame::Array(vec![
+        bulk("name"),
+        bulk("ferris"),
+        bulk("visits"),
+        bulk("12"),
+        bulk("admin"),
+        bulk("1"),
+    ]);
+
+    let user: User = from_frame(reply).unwrap();
+    assert_eq!(
+        User {
+            name: "ferris".to_string(),
+            visits: 12,
+            admin: true,
+            email: None,
+        },
+        user
+    );
+}
+
+#[test]
+fn enums_round_trip() {
+    let events = vec![
+        Event::Joined,
+        Event::Visit(3),
+        Event::Moved {
+            from: "a".to_string(),
+            to: "b".to_string(),
+        },
+    ];
+
+    let frame = to_frame(&events).unwrap();
+    assert_eq!(
+        Frame::Array(vec![
+            bulk("Joined"),
+            Frame::Map(vec![(bulk("Visit"), Frame::Integer(3))]),
+            Frame::Map(vec![(
+                bulk("Moved"),
+                Frame::Map(vec![(bulk("from"), bulk("a")), (bulk("to"), bulk("b"))]),
+            )]),
+        ]),
+        frame
+    );
+    assert_eq!(events, from_frame::<Vec<Event>>(frame).unwrap());
+}
+
+#[test]
+fn error_replies_are_reported() {
+    let err = from_frame::<User>(Frame::Error("WRONGTYPE nope".to_string())).unwrap_err();
+    assert_eq!("WRONGTYPE nope", err.to_string());
+}
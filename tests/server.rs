@@ -0,0 +1,297 @@
+mod common;
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Stdio};
+
+use common::{bulk, command, Pki};
+use mini_redis_rs::{Connection, Frame};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader, Lines};
+use tokio::net::UnixStream;
+use tokio::process::{Child, ChildStdout, Command};
+
+/// A `server` process listening on a Unix socket in a temporary directory,
+/// killed when dropped.
+struct Server {
+    process: Child,
+    dir: PathBuf,
+
+    /// The server's output, read up to the line saying it is listening. It is
+    /// kept open so that the server can go on printing.
+    stdout: Lines<BufReader<ChildStdout>>,
+}
+
+impl Server {
+    /// Starts a server with the given options on top of the Unix socket, and
+    /// waits for it to listen. Relative paths are relative to the temporary
+    /// directory.
+    async fn start(name: &str, args: &[&str]) -> Server {
+        let dir =
+            std::env::temp_dir().join(format!("mini-redis-server-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut process = Command::new(env!("CARGO_BIN_EXE_server"))
+            .args(["--port", "0", "--unixsocket"])
+            .arg(dir.join("redis.sock"))
+            .args(args)
+            .current_dir(&dir)
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+
+        let mut stdout = BufReader::new(process.stdout.take().unwrap()).lines();
+
+        loop {
+            match stdout.next_line().await.unwrap() {
+                Some(line) if line.starts_with("Listening on") => break,
+                Some(_) => {}
+                None => panic!("server exited before listening"),
+            }
+        }
+
+        Server {
+            process,
+            dir,
+            stdout,
+        }
+    }
+
+    fn socket(&self) -> PathBuf {
+        self.dir.join("redis.sock")
+    }
+
+    async fn connect(&self) -> Connection<UnixStream> {
+        Connection::new(UnixStream::connect(self.socket()).await.unwrap())
+    }
+
+    /// Waits for the server to exit, returning its exit status and the rest
+    /// of its output.
+    async fn exit(mut self) -> (ExitStatus, Vec<String>) {
+        let mut output = vec![];
+
+        while let Some(line) = self.stdout.next_line().await.unwrap() {
+            output.push(line);
+        }
+
+        (self.process.wait().await.unwrap(), output)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Sends the command `args` and returns the reply.
+async fn request<S>(client: &mut Connection<S>, args: &[&str]) -> Option<Frame>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    client.write_frame(&command(args)).await.unwrap();
+    client.read_frame().await.unwrap()
+}
+
+#[tokio::test]
+async fn unix_socket() {
+    let server = Server::start("unixsocket", &["--unixsocketperm", "700"]).await;
+
+    let mode = std::fs::metadata(server.socket())
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(0o700, mode & 0o777);
+
+    let mut client = server.connect().await;
+    assert_eq!(
+        Some(Frame::Simple("OK".to_string())),
+        request(&mut client, &["SET", "key", "value"]).await
+    );
+    assert_eq!(
+        Some(bulk("value")),
+        request(&mut client, &["GET", "key"]).await
+    );
+
+    // The socket file is removed once the server is done with it.
+    let socket = server.socket();
+    client
+        .write_frame(&command(&["SHUTDOWN", "NOSAVE"]))
+        .await
+        .unwrap();
+    assert!(server.exit().await.0.success());
+    assert!(!socket.exists());
+}
+
+#[tokio::test]
+async fn maxclients() {
+    let server = Server::start("maxclients", &["--maxclients", "2"]).await;
+
+    let mut first = server.connect().await;
+    let mut second = server.connect().await;
+    for client in [&mut first, &mut second] {
+        assert_eq!(Some(Frame::Null), request(client, &["GET", "key"]).await);
+    }
+
+    // The extra client is told why, then disconnected.
+    let mut extra = server.connect().await;
+    assert_eq!(
+        Some(Frame::Error(
+            "ERR max number of clients reached".to_string()
+        )),
+        extra.read_frame().await.unwrap()
+    );
+    assert_eq!(None, extra.read_frame().await.unwrap());
+
+    // Once a client leaves, another one may connect.
+    drop(first);
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    let mut third = server.connect().await;
+    assert_eq!(
+        Some(Frame::Null),
+        request(&mut third, &["GET", "key"]).await
+    );
+}
+
+#[tokio::test]
+async fn maxclients_over_tls() {
+    use mini_redis_rs::tls;
+    use std::sync::Arc;
+
+    let pki = Pki::new("maxclients");
+    let (cert_file, key_file) = pki.issue("server");
+
+    // Find a free port for the TLS listener.
+    let port = {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port().to_string()
+    };
+    let _server = Server::start(
+        "maxclients-tls",
+        &[
+            "--maxclients",
+            "1",
+            "--tls-port",
+            &port,
+            "--tls-cert-file",
+            cert_file.to_str().unwrap(),
+            "--tls-key-file",
+            key_file.to_str().unwrap(),
+        ],
+    )
+    .await;
+
+    let addr = format!("127.0.0.1:{}", port);
+    let config = Arc::new(tls::client_config(&pki.ca_file(), None).unwrap());
+    let connect = || tls::connect(&addr, "localhost", config.clone());
+
+    let mut client = connect().await.unwrap();
+    assert_eq!(
+        Some(Frame::Null),
+        request(&mut client, &["GET", "key"]).await
+    );
+
+    // Like over the other listeners, the extra client is told why once the
+    // handshake is done, then disconnected.
+    let mut extra = connect().await.unwrap();
+    assert_eq!(
+        Some(Frame::Error(
+            "ERR max number of clients reached".to_string()
+        )),
+        extra.read_frame().await.unwrap()
+    );
+    assert_eq!(None, extra.read_frame().await.unwrap());
+}
+
+#[tokio::test]
+async fn error_replies() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let server = Server::start("errors", &[]).await;
+
+    // Unknown and malformed commands get an error reply, and the connection
+    // stays usable.
+    let mut client = server.connect().await;
+    assert!(matches!(
+        request(&mut client, &["NOPE"]).await,
+        Some(Frame::Error(err)) if err.starts_with("ERR unknown command 'nope'")
+    ));
+    assert_eq!(
+        Some(Frame::Error(
+            "ERR wrong number of arguments for 'get' command".to_string()
+        )),
+        request(&mut client, &["GET"]).await
+    );
+    assert_eq!(
+        Some(Frame::Null),
+        request(&mut client, &["GET", "key"]).await
+    );
+
+    // So do the commands handled by the server itself.
+    for (args, err) in [
+        (&["SHUTDOWN", "MAYBE"][..], "ERR syntax error"),
+        (&["SHUTDOWN", "NOSAVE", "NOW"], "ERR syntax error"),
+        (
+            &["SHUTDOWN", "SAVE"],
+            "ERR no dbfilename configured to SAVE to",
+        ),
+    ] {
+        assert_eq!(
+            Some(Frame::Error(err.to_string())),
+            request(&mut client, args).await
+        );
+    }
+
+    // Data that is not RESP at all closes the connection, with the reason
+    // sent as an error reply using the `-` prefix.
+    let mut socket = UnixStream::connect(server.socket()).await.unwrap();
+    socket.write_all(b"*1\r\n$abc\r\n").await.unwrap();
+
+    let mut reply = String::new();
+    socket.read_to_string(&mut reply).await.unwrap();
+    assert!(reply.starts_with("-ERR Protocol error: "), "{:?}", reply);
+    assert!(reply.ends_with("\r\n") && reply.lines().count() == 1);
+}
+
+#[tokio::test]
+async fn shutdown_drains_connections() {
+    let server = Server::start("shutdown", &["--dbfilename", "dump.rdb"]).await;
+
+    let mut idle = server.connect().await;
+    assert_eq!(
+        Some(Frame::Simple("OK".to_string())),
+        request(&mut idle, &["SET", "key", "value"]).await
+    );
+
+    // Commands pipelined before `SHUTDOWN` are carried out and replied to.
+    let mut client = server.connect().await;
+    client
+        .write_frames(&[
+            command(&["SET", "other", "value"]),
+            command(&["SHUTDOWN", "NOSAVE"]),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(
+        Some(Frame::Simple("OK".to_string())),
+        client.read_frame().await.unwrap()
+    );
+
+    // Every connection is notified, then closed.
+    for client in [&mut client, &mut idle] {
+        assert_eq!(
+            Some(Frame::Error("ERR Server is shutting down".to_string())),
+            client.read_frame().await.unwrap()
+        );
+        assert_eq!(None, client.read_frame().await.unwrap());
+    }
+
+    let dump = server.dir.join("dump.rdb");
+    let (status, output) = server.exit().await;
+    assert!(status.success());
+    assert!(output.contains(&"SHUTDOWN requested, shutting down".to_string()));
+
+    // With `NOSAVE`, the data is not saved even though a file is configured.
+    assert!(!dump.exists());
+}
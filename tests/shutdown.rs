@@ -0,0 +1,20 @@
+use mini_redis_rs::Shutdown;
+use tokio::sync::broadcast;
+
+#[tokio::test]
+async fn every_listener_is_notified() {
+    let (notify, _) = broadcast::channel(1);
+    let mut first = Shutdown::new(notify.subscribe());
+    let mut second = Shutdown::new(notify.subscribe());
+    assert!(!first.is_shutdown());
+
+    // Dropping the sender is enough to signal the shutdown.
+    drop(notify);
+
+    first.recv().await;
+    second.recv().await;
+    assert!(first.is_shutdown() && second.is_shutdown());
+
+    // Once received, the signal is remembered.
+    first.recv().await;
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use std::path::Path;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use common::Pki;
+use mini_redis_rs::tls::{self, TlsAcceptor};
+use mini_redis_rs::{Connection, Frame};
+use tokio::net::TcpListener;
+
+/// Starts a server answering every frame with `+PONG` over TLS, and returns
+/// its address.
+async fn serve(cert_file: &Path, key_file: &Path, ca_file: Option<&Path>) -> String {
+    let config = tls::server_config(cert_file, key_file, ca_file).unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+
+            tokio::spawn(async move {
+                let mut connection = match acceptor.accept(socket).await {
+                    Ok(stream) => Connection::new(stream),
+                    Err(_) => return,
+                };
+
+                while let Ok(Some(_)) = connection.read_frame().await {
+                    let pong = Frame::Simple("PONG".to_string());
+                    connection.write_frame(&pong).await.unwrap();
+                }
+            });
+        }
+    });
+
+    addr
+}
+
+fn ping() -> Frame {
+    Frame::Array(vec![Frame::Bulk(Bytes::from_static(b"PING"))])
+}
+
+#[tokio::test]
+async fn frames_over_tls() {
+    let pki = Pki::new("server");
+    let (cert_file, key_file) = pki.issue("server");
+    let addr = serve(&cert_file, &key_file, None).await;
+
+    let config = tls::client_config(&pki.ca_file(), None).unwrap();
+    let mut connection = tls::connect(&addr, "localhost", Arc::new(config))
+        .await
+        .unwrap();
+
+    connection.write_frame(&ping()).await.unwrap();
+    assert_eq!(
+        Some(Frame::Simple("PONG".to_string())),
+        connection.read_frame().await.unwrap()
+    );
+}
+
+#[tokio::test]
+async fn untrusted_server_is_rejected() {
+    let pki = Pki::new("untrusted");
+    let (cert_file, key_file) = pki.issue("server");
+    let addr = serve(&cert_file, &key_file, None).await;
+
+    let other = Pki::new("other");
+    let config = tls::client_config(&other.ca_file(), None).unwrap();
+    assert!(tls::connect(&addr, "localhost", Arc::new(config))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn mutual_tls() {
+    let pki = Pki::new("mutual");
+    let (cert_file, key_file) = pki.issue("server");
+    let ca_file = pki.ca_file();
+    let addr = serve(&cert_file, &key_file, Some(&ca_file)).await;
+
+    let (client_cert, client_key) = pki.issue("client");
+    let config = tls::client_config(&ca_file, Some((&client_cert, &client_key))).unwrap();
+    let mut connection = tls::connect(&addr, "localhost", Arc::new(config))
+        .await
+        .unwrap();
+
+    connection.write_frame(&ping()).await.unwrap();
+    assert_eq!(
+        Some(Frame::Simple("PONG".to_string())),
+        connection.read_frame().await.unwrap()
+    );
+
+    // Without a client certificate, the server ends the handshake. With TLS
+    // 1.3 the client only finds out when it reads.
+    let config = tls::client_config(&ca_file, None).unwrap();
+    let res = match tls::connect(&addr, "localhost", Arc::new(config)).await {
+        Ok(mut connection) => {
+            connection.write_frame(&ping()).await.unwrap();
+            connection.read_frame().await
+        }
+        Err(err) => Err(err),
+    };
+    assert!(res.is_err());
+}